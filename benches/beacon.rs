@@ -0,0 +1,130 @@
+//! Compares the per-epoch work of a node with the fixed pairing arguments prepared once and the
+//! epoch generator cached (as the node does) against preparing them on every use.
+//!
+//! Run with `cargo bench --bench beacon --features test-support`.
+
+use ark_bls12_381::Bls12_381;
+use ark_ec::PairingEngine;
+use ark_ff::One;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use rayon::prelude::*;
+use std::ops::Neg;
+
+use grandline::{
+    beacon::{
+        check_consistency, compute_sigma, epoch_generator, verify_batch, verify_proof, Generator,
+    },
+    message::SigmaMessage,
+    test_support::{self, Epoch},
+};
+
+type E = Bls12_381;
+
+const NUM_PARTICIPANTS: usize = 16;
+const NUM_FAULTS: usize = 7;
+const EPOCH: u64 = 7;
+
+fn epoch() -> Epoch<E> {
+    test_support::epoch(NUM_PARTICIPANTS, NUM_FAULTS, EPOCH)
+}
+
+/// The consistency check without any preparation: all four pairing arguments are prepared for
+/// every message.
+fn check_consistency_unprepared(epoch: &Epoch<E>, message: &SigmaMessage<E>) -> bool {
+    let generator = epoch_generator::<E>(message.epoch);
+    let pairs = [
+        (
+            epoch.commitments[message.id].part2.neg().into(),
+            generator.into(),
+        ),
+        (epoch.config.srs.g1.neg().into(), message.sigma.0.into()),
+    ];
+
+    let prod = <E as PairingEngine>::product_of_pairings(pairs.iter());
+
+    (message.sigma.1 * prod).is_one()
+}
+
+fn bench_compute_sigma(c: &mut Criterion) {
+    let epoch = epoch();
+    let sk = epoch.sks[0].into();
+    let mut group = c.benchmark_group("compute_sigma");
+
+    group.bench_function("uncached", |b| {
+        b.iter(|| {
+            let generator = Generator::new(EPOCH);
+            compute_sigma(
+                &epoch.config,
+                0,
+                &epoch.sks[0].into(),
+                epoch.a_is[0],
+                EPOCH,
+                &generator,
+            )
+        })
+    });
+    group.bench_function("cached", |b| {
+        b.iter(|| {
+            compute_sigma(
+                &epoch.config,
+                0,
+                &sk,
+                epoch.a_is[0],
+                EPOCH,
+                &epoch.generator,
+            )
+        })
+    });
+
+    group.finish();
+}
+
+fn bench_verify(c: &mut Criterion) {
+    let epoch = epoch();
+    let mut group = c.benchmark_group("verify_sigmas");
+
+    group.bench_function("unprepared", |b| {
+        b.iter(|| {
+            epoch.messages.par_iter().all(|message| {
+                verify_proof(
+                    &epoch.config,
+                    &epoch.commitments[message.id],
+                    epoch_generator::<E>(message.epoch),
+                    message,
+                ) && check_consistency_unprepared(&epoch, message)
+            })
+        })
+    });
+    group.bench_function("prepared", |b| {
+        b.iter(|| {
+            epoch.messages.par_iter().all(|message| {
+                verify_proof(
+                    &epoch.config,
+                    &epoch.commitments[message.id],
+                    epoch.generator.point,
+                    message,
+                ) && check_consistency(&epoch.precomputed, &epoch.generator, message)
+            })
+        })
+    });
+    group.bench_function("batch", |b| {
+        b.iter_batched(
+            || epoch.messages.clone(),
+            |messages| {
+                verify_batch(
+                    &epoch.config,
+                    &epoch.commitments,
+                    &epoch.precomputed,
+                    &epoch.generator,
+                    messages,
+                )
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_compute_sigma, bench_verify);
+criterion_main!(benches);
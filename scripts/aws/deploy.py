@@ -1,25 +1,53 @@
+import argparse
+import json
 import subprocess
 import os
-import sys
 from parse import parse_files
 
-print("Creating ip file...")
-filename_in = "ips.txt"
-filename_out = "scripts/aws/ips"
+parser = argparse.ArgumentParser()
+parser.add_argument("faults", nargs="?", type=int,
+                    help="number of faults the setup tolerates, n / 2 - 1 by default")
+parser.add_argument("--install", action="store_true",
+                    help="install the nodes and generate their identities")
+parser.add_argument("--dkg", action="store_true",
+                    help="generate a new setup and run the key generation on the nodes")
+args = parser.parse_args()
 
-f_in = open(filename_in, "r")
-f_out = open(filename_out, "w")
+filename_in = "ips.txt"
+filename_out = "scripts/aws/committee.json"
 
-for i, line in enumerate(f_in):
-    port = 9000 + i
-    f_out.write(line.splitlines()[0] + ":" + str(port) + "\n")
+with open(filename_in, "r") as f_in:
+    hosts = [line.strip() for line in f_in if line.strip()]
 
-f_in.close()
-f_out.close()
+faults = args.faults if args.faults is not None else len(hosts) // 2 - 1
+setup = f'configs/{len(hosts)}_{faults}'
 
-if len(sys.argv) > 1:
+if args.install:
     print("Installing...")
-    subprocess.run("bash scripts/aws/do_setup.sh", shell=True)
+    subprocess.run('bash scripts/aws/do_setup.sh', shell=True)
+
+if args.dkg:
+    # The committee lists the public keys every host generated during the installation.
+    print("Creating committee file...")
+    members = []
+    for idx, host in enumerate(hosts):
+        with open(f'scripts/aws/members/{idx}.json', "r") as f:
+            member = json.load(f)
+        member["id"] = idx
+        member["host"] = host
+        member["port"] = 9000 + idx
+        members.append(member)
+
+    with open(filename_out, "w") as f_out:
+        json.dump({"members": members}, f_out, indent=2)
+
+    print("Generating setup...")
+    subprocess.run("cargo build --bin generator --release", shell=True)
+    subprocess.run(
+        f'target/release/generator --force config {filename_out} {faults}', shell=True)
+
+    print("Generating keys...")
+    subprocess.run(f'bash scripts/aws/do_dkg.sh {setup}', shell=True)
 
 print("Deleting old logs...")
 for file in os.scandir("logs"):
@@ -27,7 +55,7 @@ for file in os.scandir("logs"):
         os.unlink(file.path)
 
 print("Running experiments...")
-subprocess.run("bash scripts/aws/do_test.sh", shell=True)
+subprocess.run(f'bash scripts/aws/do_test.sh {setup}', shell=True)
 
 print("Parsing logs...")
 timedelta, counter = parse_files("logs/")
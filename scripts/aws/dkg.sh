@@ -0,0 +1,3 @@
+killall -9 app
+cd grandline
+timeout 300 ./target/release/app $1 committee.json 2 --setup $2 --dkg --identity identity --force &> dkg.log
\ No newline at end of file
@@ -0,0 +1,38 @@
+# Usage: do_dkg.sh <setup prefix, e.g. configs/4_1> [ip file] [committee file]
+# Hands the committee and the config to every host and runs the key generation on all of them.
+SETUP=$1
+IP_FILE=${2:-"ips.txt"}
+COMMITTEE_FILE=${3:-"scripts/aws/committee.json"}
+NAME=$(basename $SETUP)
+IPS=()
+
+while IFS= read -r line; do
+  IPS+=($line)
+done < $IP_FILE
+
+for ip in "${IPS[@]}"
+do
+  echo $ip
+  ssh -i "randpiper.pem" ubuntu@$ip "mkdir -p grandline/configs; cd grandline; cat > committee.json" < $COMMITTEE_FILE
+  ssh -i "randpiper.pem" ubuntu@$ip "cat > grandline/configs/${NAME}cfg" < ${SETUP}cfg
+done
+
+idx=0
+
+for ip in "${IPS[@]}"
+do
+    ssh -i "randpiper.pem" -t ubuntu@$ip 'bash -ls --' < scripts/aws/dkg.sh $idx configs/$NAME &
+    idx=$(($idx+1))
+done
+
+wait
+
+idx=0
+
+for ip in "${IPS[@]}"
+do
+  scp -i "randpiper.pem" ubuntu@$ip:grandline/dkg.log ./logs/$idx.dkg &
+  idx=$(($idx+1))
+done
+
+wait
\ No newline at end of file
@@ -1,5 +1,6 @@
-IP_FILE=${2:-"ips.txt"}
-IP_AWS_FILE=${2:-"scripts/aws/ips"}
+# Usage: do_setup.sh [ip file]
+# Installs the nodes and collects the committee entry of every node in scripts/aws/members.
+IP_FILE=${1:-"ips.txt"}
 IPS=()
 
 while IFS= read -r line; do
@@ -14,10 +15,13 @@ done
 
 wait
 
+mkdir -p scripts/aws/members
+idx=0
+
 for ip in "${IPS[@]}"
 do
-  echo $ip
-  ssh -i "randpiper.pem" ubuntu@$ip "cd grandline; cat > ips.txt" < $IP_AWS_FILE &
+  scp -i "randpiper.pem" ubuntu@$ip:grandline/member.json scripts/aws/members/$idx.json &
+  idx=$(($idx+1))
 done
 
 wait
\ No newline at end of file
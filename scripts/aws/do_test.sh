@@ -1,3 +1,5 @@
+# Usage: do_test.sh <setup prefix, e.g. configs/4_1> [ip file]
+SETUP=$1
 IP_FILE=${2:-"ips.txt"}
 IPS=()
 
@@ -9,7 +11,7 @@ idx=0
 
 for ip in "${IPS[@]}"
 do
-    ssh -i "randpiper.pem" -t ubuntu@$ip 'bash -ls --' < scripts/aws/test.sh $idx &
+    ssh -i "randpiper.pem" -t ubuntu@$ip 'bash -ls --' < scripts/aws/test.sh $idx configs/$(basename $SETUP) &
     idx=$(($idx+1))
 done
 
@@ -12,4 +12,7 @@ git clone https://github.com/sochsenreither/grandline.git
 cd grandline
 git pull
 
-cargo build --bin app --release
+cargo build --bin app --bin generator --release
+
+# Every host generates its own identity. Only the committee entry with the public keys leaves it.
+./target/release/generator --force keygen identity > member.json
\ No newline at end of file
@@ -1,3 +1,4 @@
 killall -9 app
 cd grandline
-timeout 300 ./target/release/app $1 ips.txt 2 &> output.log
\ No newline at end of file
+rm -f beacons.history
+timeout 300 ./target/release/app $1 committee.json 2 --setup $2 --history beacons.history &> output.log
\ No newline at end of file
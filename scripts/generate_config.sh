@@ -1,3 +1,3 @@
 cargo build --bin generator --release
 
-./target/release/generator $1
\ No newline at end of file
+./target/release/generator local $@
\ No newline at end of file
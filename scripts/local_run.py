@@ -5,34 +5,22 @@ import os
 from aws.parse import parse_files
 
 
-def create_ip_files(nodes: int, filename: str):
-    print("Creating ip files...")
-    prefix = "127.0.0.1:"
-    ips = []
-
-    with open(filename, "w", encoding="utf-8") as f:
-        for i in range(nodes):
-            port = 9000 + i
-            f.write(prefix + str(port) + '\n')
-            ips.append(prefix + str(port) + '\n')
-
-
 def delete_logs(path: str):
     print("Deleting old logs...")
     try:
         for file in os.scandir(path):
-            if file.name.endswith(".log"):
+            if file.name.endswith((".log", ".dkg", ".history")):
                 os.unlink(file.path)
     except:
         os.mkdir(path)
 
 
-def generate_config(nodes: int):
+def generate_config(nodes: int, faults: int):
     print("Generating configs...")
     subprocess.run("cargo build --bin generator --release",
                    shell=True, check=False)
     subprocess.run(
-        f'target/release/generator {nodes}', shell=True, check=False)
+        f'target/release/generator local {nodes} {faults} --force', shell=True, check=False)
 
 
 def compile_project(app_name: str):
@@ -41,14 +29,31 @@ def compile_project(app_name: str):
         f'cargo build --bin {app_name} --release', shell=True, check=False)
 
 
-def test_run(nodes: int, app_name: str, filename: str, duration: int):
+def generate_keys(nodes: int, app_name: str, setup: str):
+    # every node generates its key share together with the others
+    subprocess.run(f'killall -9 {app_name}', shell=True, check=False)
+
+    print("Generating keys...")
+    processes = []
+    for i in range(nodes):
+        cmd = (f'target/release/app {i} {setup}committee.json 2 --setup {setup} '
+               f'--dkg --force &> logs/{i}.dkg')
+        p = subprocess.Popen(cmd, shell=True)
+        processes.append(p)
+
+    for p in processes:
+        p.wait()
+
+
+def test_run(nodes: int, app_name: str, setup: str, duration: int):
     # start all nodes
     subprocess.run(f'killall -9 {app_name}', shell=True, check=False)
 
     print("Starting nodes...")
     processes = []
     for i in range(nodes):
-        cmd = f'target/release/app {i} {filename} 2 &> logs/{i}.log'
+        cmd = (f'target/release/app {i} {setup}committee.json 2 --setup {setup} '
+               f'--history logs/{i}.history &> logs/{i}.log')
         p = subprocess.Popen(cmd, shell=True)
         processes.append(p)
 
@@ -72,19 +77,23 @@ def parse_logs(path: str):
 
 def main():
     if len(sys.argv) < 3:
-        print("Argument 1: number of nodes, Argument 2: duration of test run")
+        print("Argument 1: number of nodes, Argument 2: duration of test run, "
+              "Argument 3 (optional): number of faults, n / 2 - 1 by default")
         return
     nodes = int(sys.argv[1])
     duration = int(sys.argv[2])
+    faults = int(sys.argv[3]) if len(sys.argv) > 3 else nodes // 2 - 1
     binary_name = "app"
-    filename = "local_ips.txt"
+    # The generator writes the node identities and a committee file listening on
+    # 127.0.0.1:9000 + id next to the setup.
+    setup = f'configs/{nodes}_{faults}'
     logs = "logs"
 
     delete_logs(logs)
-    create_ip_files(nodes, filename)
     compile_project(binary_name)
-    generate_config(nodes)
-    test_run(nodes, binary_name, filename, duration)
+    generate_config(nodes, faults)
+    generate_keys(nodes, binary_name, setup)
+    test_run(nodes, binary_name, setup, duration)
     parse_logs(logs)
 
 
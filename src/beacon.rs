@@ -0,0 +1,356 @@
+use ark_ec::{msm::VariableBaseMSM, AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{Field, One, PrimeField};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
+use rand::{thread_rng, Rng};
+use rayon::prelude::*;
+use sha3::{
+    digest::{ExtendableOutput, Update, XofReader},
+    Shake256,
+};
+use std::{
+    collections::HashSet,
+    ops::{Mul, Neg},
+};
+
+use optrand_pvss::{
+    modified_scrape::{config::Config, poly::lagrange_interpolation_gt},
+    nizk::{
+        dleq::{srs::SRS as DLEQSRS, DLEQProof},
+        scheme::NIZKProof,
+        utils::hash::hash_to_group,
+    },
+    ComGroup, Scalar, GT,
+};
+
+use crate::{config::Commitment, message::SigmaMessage};
+
+pub const PERSONA: &[u8] = b"OnePiece";
+pub const LAMBDA: usize = 256; // main security parameter
+
+#[cfg(test)]
+#[path = "tests/beacon_tests.rs"]
+mod beacon_tests;
+
+/// A finished beacon value for a single epoch, as delivered to applications. The contributing
+/// sigma messages (including their DLEQ proofs) allow anyone holding the public setup to check
+/// the value with `verify_beacon`.
+#[derive(Debug, Clone)]
+pub struct BeaconOutput<E: PairingEngine> {
+    pub epoch: u64,
+    pub value: [u8; LAMBDA >> 3],
+    pub sigma: GT<E>,
+    pub contributors: Vec<usize>,
+    pub shares: Vec<SigmaMessage<E>>,
+}
+
+// The contributors are not encoded, they are recovered from the shares.
+impl<E: PairingEngine> CanonicalSerialize for BeaconOutput<E> {
+    fn serialize<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
+        self.epoch.serialize(&mut writer)?;
+        self.value.to_vec().serialize(&mut writer)?;
+        self.sigma.serialize(&mut writer)?;
+        self.shares.serialize(&mut writer)
+    }
+
+    fn serialized_size(&self) -> usize {
+        self.epoch.serialized_size()
+            + self.value.to_vec().serialized_size()
+            + self.sigma.serialized_size()
+            + self.shares.serialized_size()
+    }
+}
+
+impl<E: PairingEngine> CanonicalDeserialize for BeaconOutput<E> {
+    fn deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+        let epoch = u64::deserialize(&mut reader)?;
+        let value = Vec::<u8>::deserialize(&mut reader)?;
+        let sigma = GT::<E>::deserialize(&mut reader)?;
+        let shares = Vec::<SigmaMessage<E>>::deserialize(&mut reader)?;
+
+        Ok(Self {
+            epoch,
+            value: value
+                .try_into()
+                .map_err(|_| SerializationError::InvalidData)?,
+            sigma,
+            contributors: shares.iter().map(|message| message.id).collect(),
+            shares,
+        })
+    }
+}
+
+/// Pairing arguments that are fixed for a committee, prepared once at startup and reused in
+/// every epoch.
+pub struct Precomputed<E: PairingEngine> {
+    neg_g1: E::G1Prepared,
+    neg_part2s: Vec<E::G1Prepared>,
+}
+
+impl<E: PairingEngine> Precomputed<E> {
+    pub fn new(config: &Config<E>, commitments: &[Commitment<E>]) -> Self {
+        Self {
+            neg_g1: config.srs.g1.neg().into(),
+            neg_part2s: commitments
+                .iter()
+                .map(|commitment| commitment.part2.neg().into())
+                .collect(),
+        }
+    }
+}
+
+/// The generator of an epoch along with its preparation for pairings, which is shared by all
+/// sigmas of the epoch.
+#[derive(Clone)]
+pub struct Generator<E: PairingEngine> {
+    pub point: ComGroup<E>,
+    pub prepared: E::G2Prepared,
+}
+
+impl<E: PairingEngine> Generator<E> {
+    pub fn new(epoch: u64) -> Self {
+        let point = epoch_generator::<E>(epoch);
+
+        Self {
+            point,
+            prepared: point.into(),
+        }
+    }
+}
+
+/// Derives the generator of a given epoch by hashing the epoch number into the group.
+pub fn epoch_generator<E: PairingEngine>(epoch: u64) -> ComGroup<E> {
+    hash_to_group::<ComGroup<E>>(PERSONA, &epoch.to_le_bytes())
+        .unwrap()
+        .into_affine()
+}
+
+/// Computes the sigma message of node `id` for an epoch: sigma_i = (g_r^a_i, e(sk_i, g_r)), along
+/// with a DLEQ proof that sigma_i.0 and the commitment g2^a_i have the same exponent.
+pub fn compute_sigma<E: PairingEngine>(
+    config: &Config<E>,
+    id: usize,
+    sk: &E::G1Prepared,
+    a_i: Scalar<E>,
+    epoch: u64,
+    generator: &Generator<E>,
+) -> SigmaMessage<E> {
+    let sigma = (
+        // Spelled out, since std::ops::Mul is in scope as well.
+        <ComGroup<E> as AffineCurve>::mul(&generator.point, a_i).into_affine(),
+        <E as PairingEngine>::product_of_pairings(
+            [(sk.clone(), generator.prepared.clone())].iter(),
+        ),
+    );
+
+    let srs = DLEQSRS::<ComGroup<E>, ComGroup<E>> {
+        g_public_key: generator.point,
+        h_public_key: config.srs.g2,
+    };
+    let dleq = DLEQProof { srs };
+    let pi = dleq.prove(&mut thread_rng(), &a_i).unwrap();
+
+    SigmaMessage {
+        epoch,
+        id,
+        sigma,
+        pi,
+    }
+}
+
+/// Checks that a value lies in the order r subgroup of the target field, i.e. that it is a
+/// valid pairing output. Deserializing a GT element does not check this.
+pub fn in_target_group<E: PairingEngine>(value: &GT<E>) -> bool {
+    value.pow(Scalar::<E>::characteristic()).is_one()
+}
+
+/// Given a message verify its DLEQ proof against the sender's commitment.
+/// Returns true if the proof is correct.
+pub fn verify_proof<E: PairingEngine>(
+    config: &Config<E>,
+    commitment: &Commitment<E>,
+    generator: ComGroup<E>,
+    message: &SigmaMessage<E>,
+) -> bool {
+    let stmnt = (message.sigma.0, commitment.part1);
+    let srs = DLEQSRS::<ComGroup<E>, ComGroup<E>> {
+        g_public_key: generator,
+        h_public_key: config.srs.g2,
+    };
+    let dleq = DLEQProof::from_srs(srs).unwrap();
+
+    dleq.verify(&stmnt, &message.pi).is_ok()
+}
+
+/// Checks consistency of a received SigmaMessage with the commitments
+/// provided by the same user during the Commitment Phase.
+pub fn check_consistency<E: PairingEngine>(
+    precomputed: &Precomputed<E>,
+    generator: &Generator<E>,
+    message: &SigmaMessage<E>,
+) -> bool {
+    let pairs = [
+        (
+            precomputed.neg_part2s[message.id].clone(),
+            generator.prepared.clone(),
+        ),
+        (precomputed.neg_g1.clone(), message.sigma.0.into()),
+    ];
+
+    let prod = <E as PairingEngine>::product_of_pairings(pairs.iter());
+
+    (message.sigma.1).mul(prod).is_one()
+}
+
+/// Checks the consistency of many sigma messages of the same epoch at once. The individual
+/// equations sigma_i.1 * e(-part2_i, g_r) * e(-g1, sigma_i.0) = 1 are combined with random 128 bit
+/// weights, which needs two multi-exponentiations and a single product of two pairings.
+///
+/// The combination is only sound if every sigma_i.1 lies in GT (see `in_target_group`): a factor
+/// of small order, such as -1, can vanish under a random even weight. For messages that passed
+/// that check, a forged message makes the batch fail except with probability 2^-128.
+pub fn batch_check_consistency<E: PairingEngine, R: Rng>(
+    commitments: &[Commitment<E>],
+    precomputed: &Precomputed<E>,
+    generator: &Generator<E>,
+    messages: &[SigmaMessage<E>],
+    rng: &mut R,
+) -> bool {
+    let mut weights = Vec::with_capacity(messages.len());
+    let mut part2s = Vec::with_capacity(messages.len());
+    let mut sigmas = Vec::with_capacity(messages.len());
+    let mut product = GT::<E>::one();
+
+    for message in messages {
+        let weight = rng.gen::<u128>();
+        product *= &message.sigma.1.pow(&[weight as u64, (weight >> 64) as u64]);
+
+        weights.push(Scalar::<E>::from(weight).into_repr());
+        part2s.push(commitments[message.id].part2);
+        sigmas.push(message.sigma.0);
+    }
+
+    let part2 = VariableBaseMSM::multi_scalar_mul(&part2s, &weights);
+    let sigma = VariableBaseMSM::multi_scalar_mul(&sigmas, &weights);
+
+    let pairs = [
+        (part2.neg().into_affine().into(), generator.prepared.clone()),
+        (precomputed.neg_g1.clone(), sigma.into_affine().into()),
+    ];
+
+    product
+        .mul(<E as PairingEngine>::product_of_pairings(pairs.iter()))
+        .is_one()
+}
+
+/// Verifies sigma messages of the same epoch and splits them into valid and invalid ones. The
+/// consistency checks are batched. Only if the batch fails, every message is checked on its own
+/// to find the bad senders.
+///
+/// The DLEQ proofs and the subgroup checks the batch relies on are still done one by one, since
+/// optrand_pvss does not expose the challenge derivation needed to combine the proofs, but they
+/// are spread over the rayon thread pool.
+pub fn verify_batch<E: PairingEngine>(
+    config: &Config<E>,
+    commitments: &[Commitment<E>],
+    precomputed: &Precomputed<E>,
+    generator: &Generator<E>,
+    messages: Vec<SigmaMessage<E>>,
+) -> (Vec<SigmaMessage<E>>, Vec<SigmaMessage<E>>) {
+    let (proven, mut invalid): (Vec<_>, Vec<_>) = messages.into_par_iter().partition(|message| {
+        in_target_group::<E>(&message.sigma.1)
+            && verify_proof(config, &commitments[message.id], generator.point, message)
+    });
+
+    if batch_check_consistency(
+        commitments,
+        precomputed,
+        generator,
+        &proven,
+        &mut thread_rng(),
+    ) {
+        return (proven, invalid);
+    }
+
+    let (valid, inconsistent): (Vec<_>, Vec<_>) = proven
+        .into_par_iter()
+        .partition(|message| check_consistency(precomputed, generator, message));
+    invalid.extend(inconsistent);
+
+    (valid, invalid)
+}
+
+/// Reconstructs sigma := e(g_r, SK) from (id, sigma_i) pairs by interpolating in the exponent.
+pub fn reconstruct_sigma<E: PairingEngine>(
+    config: &Config<E>,
+    shares: &[(usize, GT<E>)],
+) -> Option<GT<E>> {
+    let evals = shares.iter().map(|(_, s)| *s).collect::<Vec<_>>();
+    let points = shares
+        .iter()
+        .map(|(i, _)| (*i + 1) as u64) // indices must be in {1, ..., n}
+        .collect::<Vec<_>>();
+
+    lagrange_interpolation_gt::<E>(&evals, &points, config.degree as u64).ok()
+}
+
+/// Generates the beacon value out of a reconstructed sigma.
+pub fn beacon_value<E: PairingEngine>(sigma: &GT<E>) -> [u8; LAMBDA >> 3] {
+    let mut hasher = Shake256::default();
+    let mut sigma_bytes = Vec::new();
+    let _ = sigma.serialize(&mut sigma_bytes);
+    hasher.update(&sigma_bytes[..]);
+    let mut reader = hasher.finalize_xof();
+    let mut value = [0_u8; LAMBDA >> 3];
+    XofReader::read(&mut reader, &mut value);
+
+    value
+}
+
+/// Verifies a beacon output for the given epoch using only public setup material. Checks that
+/// at least t+1 distinct qualified contributors provided valid and consistent sigmas, and that
+/// both the reconstructed sigma and the beacon value match the claimed ones.
+pub fn verify_beacon<E: PairingEngine>(
+    config: &Config<E>,
+    commitments: &[Commitment<E>],
+    precomputed: &Precomputed<E>,
+    qual: &HashSet<usize>,
+    epoch: u64,
+    output: &BeaconOutput<E>,
+) -> bool {
+    if output.epoch != epoch || output.shares.len() < config.degree + 1 {
+        return false;
+    }
+
+    let mut seen = HashSet::new();
+    for message in &output.shares {
+        if message.epoch != epoch
+            || message.id >= commitments.len()
+            || !qual.contains(&message.id)
+            || !seen.insert(message.id)
+        {
+            return false;
+        }
+    }
+
+    let generator = Generator::new(epoch);
+    let (valid, invalid) = verify_batch(
+        config,
+        commitments,
+        precomputed,
+        &generator,
+        output.shares.clone(),
+    );
+    if !invalid.is_empty() {
+        return false;
+    }
+
+    let shares = valid
+        .iter()
+        .map(|message| (message.id, message.sigma.1))
+        .collect::<Vec<_>>();
+
+    match reconstruct_sigma(config, &shares) {
+        Some(sigma) => sigma == output.sigma && beacon_value::<E>(&sigma) == output.value,
+        None => false,
+    }
+}
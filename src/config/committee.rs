@@ -0,0 +1,237 @@
+use std::{
+    collections::HashSet,
+    fmt, fs, io,
+    net::{SocketAddr, ToSocketAddrs},
+    path::Path,
+};
+
+use ark_ec::PairingEngine;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use serde::{Deserialize, Serialize};
+use sha3::{
+    digest::{ExtendableOutput, Update, XofReader},
+    Shake256,
+};
+
+use optrand_pvss::EncGroup;
+
+/// Length of a static Noise (X25519) public key.
+const NOISE_KEY_LENGTH: usize = 32;
+
+const HASH_PERSONA: &[u8] = b"GRandLineCommittee";
+
+/// Upper bound on the number of epochs nodes may broadcast sigmas for in advance.
+pub const MAX_PIPELINE: u64 = 16;
+
+#[cfg(test)]
+#[path = "../tests/committee_tests.rs"]
+mod committee_tests;
+
+/// A single committee member as listed in the committee file.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Member {
+    pub id: usize,
+    /// DNS name or IP address the other nodes connect to.
+    pub host: String,
+    pub port: u16,
+    /// Hex-encoded authentication public key.
+    pub auth_key: String,
+    /// Hex-encoded static Noise public key.
+    pub noise_key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Address to bind to, if it differs from the advertised host and port.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub listen: Option<SocketAddr>,
+}
+
+impl Member {
+    /// Name of the member used in log and error messages.
+    pub fn name(&self) -> String {
+        match &self.label {
+            Some(label) => format!("{} ({})", self.id, label),
+            None => format!("{}", self.id),
+        }
+    }
+}
+
+/// Protocol parameters every member of the committee must agree on. They are stored in the
+/// committee file next to the members and covered by the committee hash, so a node whose file
+/// differs from the one the setup was generated for refuses to start.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Parameters {
+    /// Encrypt the traffic between nodes.
+    #[serde(default)]
+    pub encrypt: bool,
+    /// Number of epochs past the current one nodes compute and broadcast sigmas for in advance.
+    #[serde(default)]
+    pub pipeline: u64,
+}
+
+/// On-disk layout of the committee file.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CommitteeFile {
+    #[serde(flatten)]
+    pub parameters: Parameters,
+    pub members: Vec<Member>,
+}
+
+#[derive(Debug)]
+pub enum CommitteeError {
+    Io(String, io::Error),
+    Parse(String, serde_json::Error),
+    Empty,
+    DuplicateId(usize),
+    MissingId(usize),
+    InvalidPort(usize),
+    InvalidKey(usize, &'static str),
+    InvalidPipeline(u64),
+    Resolve(String, io::Error),
+    NoAddress(String),
+}
+
+impl fmt::Display for CommitteeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommitteeError::Io(path, e) => write!(f, "cannot read committee file {}: {}", path, e),
+            CommitteeError::Parse(path, e) => write!(f, "invalid committee file {}: {}", path, e),
+            CommitteeError::Empty => write!(f, "committee has no members"),
+            CommitteeError::DuplicateId(id) => write!(f, "member {} is listed twice", id),
+            CommitteeError::MissingId(id) => write!(f, "member {} is missing", id),
+            CommitteeError::InvalidPort(id) => write!(f, "member {} has an invalid port", id),
+            CommitteeError::InvalidKey(id, key) => {
+                write!(f, "member {} has an invalid {}", id, key)
+            }
+            CommitteeError::InvalidPipeline(pipeline) => write!(
+                f,
+                "pipeline of {} epochs exceeds the maximum of {}",
+                pipeline, MAX_PIPELINE
+            ),
+            CommitteeError::Resolve(host, e) => write!(f, "cannot resolve {}: {}", host, e),
+            CommitteeError::NoAddress(host) => write!(f, "{} did not resolve to any address", host),
+        }
+    }
+}
+
+/// The committee running the beacon, ordered by id, with decoded public keys.
+#[derive(Clone, Debug)]
+pub struct Committee<E: PairingEngine> {
+    pub parameters: Parameters,
+    pub members: Vec<Member>,
+    pub auth_keys: Vec<EncGroup<E>>,
+    pub noise_keys: Vec<Vec<u8>>,
+}
+
+/// Reads a committee file. Its members are validated by `Committee::new`, once the curve of the
+/// setup is known.
+pub fn read_committee<P: AsRef<Path>>(path: P) -> Result<CommitteeFile, CommitteeError> {
+    let display = path.as_ref().display().to_string();
+    let bytes = fs::read(&path).map_err(|e| CommitteeError::Io(display.clone(), e))?;
+
+    serde_json::from_slice::<CommitteeFile>(&bytes).map_err(|e| CommitteeError::Parse(display, e))
+}
+
+impl<E: PairingEngine> Committee<E> {
+    /// Validates the parameters and members of a committee file and decodes the members' keys.
+    /// Ids must be unique and cover 0..n, and all keys must decode.
+    pub fn new(file: CommitteeFile) -> Result<Self, CommitteeError> {
+        if file.parameters.pipeline > MAX_PIPELINE {
+            return Err(CommitteeError::InvalidPipeline(file.parameters.pipeline));
+        }
+
+        let mut members = file.members;
+        if members.is_empty() {
+            return Err(CommitteeError::Empty);
+        }
+
+        let mut ids = HashSet::new();
+        for member in &members {
+            if !ids.insert(member.id) {
+                return Err(CommitteeError::DuplicateId(member.id));
+            }
+        }
+        if let Some(id) = (0..members.len()).find(|id| !ids.contains(id)) {
+            return Err(CommitteeError::MissingId(id));
+        }
+        members.sort_by_key(|member| member.id);
+
+        let mut auth_keys = Vec::with_capacity(members.len());
+        let mut noise_keys = Vec::with_capacity(members.len());
+        for member in &members {
+            if member.port == 0 {
+                return Err(CommitteeError::InvalidPort(member.id));
+            }
+
+            let auth_key = hex::decode(&member.auth_key)
+                .ok()
+                .and_then(|bytes| EncGroup::<E>::deserialize(&*bytes).ok())
+                .ok_or(CommitteeError::InvalidKey(member.id, "authentication key"))?;
+            let noise_key = hex::decode(&member.noise_key)
+                .ok()
+                .filter(|bytes| bytes.len() == NOISE_KEY_LENGTH)
+                .ok_or(CommitteeError::InvalidKey(member.id, "noise key"))?;
+
+            auth_keys.push(auth_key);
+            noise_keys.push(noise_key);
+        }
+
+        Ok(Self {
+            parameters: file.parameters,
+            members,
+            auth_keys,
+            noise_keys,
+        })
+    }
+
+    /// Encodes the committee file.
+    pub fn to_json(&self) -> String {
+        let file = CommitteeFile {
+            parameters: self.parameters,
+            members: self.members.clone(),
+        };
+
+        serde_json::to_string_pretty(&file).unwrap() + "\n"
+    }
+
+    /// Hash of the protocol parameters and the members' public keys, in id order. Hosts, ports
+    /// and labels are left out, so that they can be changed without regenerating the setup.
+    pub fn hash(&self) -> [u8; 32] {
+        let mut hasher = Shake256::default();
+        hasher.update(HASH_PERSONA);
+        hasher.update(&[self.parameters.encrypt as u8]);
+        hasher.update(&self.parameters.pipeline.to_le_bytes());
+        hasher.update(&(self.members.len() as u64).to_le_bytes());
+        for (auth_key, noise_key) in self.auth_keys.iter().zip(&self.noise_keys) {
+            let mut bytes = Vec::new();
+            auth_key.serialize(&mut bytes).unwrap();
+            hasher.update(&bytes);
+            hasher.update(noise_key);
+        }
+
+        let mut reader = hasher.finalize_xof();
+        let mut hash = [0_u8; 32];
+        XofReader::read(&mut reader, &mut hash);
+        hash
+    }
+
+    /// Resolves the advertised address of every member, in id order.
+    pub fn resolve(&self) -> Result<Vec<SocketAddr>, CommitteeError> {
+        self.members
+            .iter()
+            .map(|member| {
+                (member.host.as_str(), member.port)
+                    .to_socket_addrs()
+                    .map_err(|e| CommitteeError::Resolve(member.host.clone(), e))?
+                    .next()
+                    .ok_or_else(|| CommitteeError::NoAddress(member.host.clone()))
+            })
+            .collect()
+    }
+}
+
+/// Hex-encodes a serializable key for the committee file.
+pub fn encode_key<T: CanonicalSerialize>(key: &T) -> String {
+    let mut bytes = Vec::new();
+    key.serialize(&mut bytes).unwrap();
+    hex::encode(bytes)
+}
@@ -1,35 +1,139 @@
 use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
 use ark_ff::{One, UniformRand};
-use ark_poly::{polynomial::UVPolynomial, Polynomial};
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError};
-use ark_std::collections::BTreeMap;
 
-use rand::thread_rng;
+use rand::{thread_rng, Rng};
 use std::{
     collections::HashSet,
-    fs::{self, File},
-    io::{self, BufRead, Write},
-    marker::PhantomData,
-    net::SocketAddr,
+    ffi::OsString,
+    fmt, fs,
+    io::{self, Write},
     ops::Neg,
-    path::Path,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    committee::{encode_key, Committee, CommitteeFile, Member, Parameters},
+    container::{seal, unseal, ContainerError, Header, Kind},
+    curve::{curve_name, Curve},
 };
 
 use optrand_pvss::{
-    generate_production_keypair,
-    modified_scrape::{
-        config::Config, dealer::Dealer, decryption::DecryptedShare, errors::PVSSError, node::Node,
-        participant::Participant, poly::Polynomial as Poly, pvss::PVSSCore, srs::SRS,
-    },
-    signature::{scheme::SignatureScheme, schnorr::srs::SRS as SCHSRS, schnorr::SchnorrSignature},
+    modified_scrape::{config::Config, srs::SRS},
     ComGroup, EncGroup, Scalar,
 };
 
+pub const NOISE_PARAMS: &str = "Noise_XX_25519_ChaChaPoly_BLAKE2s";
+
+#[derive(Debug)]
+pub enum SetupError {
+    Io(PathBuf, io::Error),
+    Corrupt(PathBuf, SerializationError),
+    Format(PathBuf, ContainerError),
+    Exists(PathBuf),
+    Invalid(String),
+}
+
+impl fmt::Display for SetupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SetupError::Io(path, e) if e.kind() == io::ErrorKind::NotFound => {
+                write!(f, "missing file {}", path.display())
+            }
+            SetupError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            SetupError::Corrupt(path, e) => write!(f, "{} is corrupt: {}", path.display(), e),
+            SetupError::Format(path, e) => write!(f, "{}: {}", path.display(), e),
+            SetupError::Exists(path) => write!(
+                f,
+                "{} already exists, use --force to overwrite it",
+                path.display()
+            ),
+            SetupError::Invalid(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// Locations of the setup files of one committee. All files share a common prefix, e.g.
+/// `configs/4_1cfg`, `configs/4_1pks`, `configs/4_1sk0`.
+#[derive(Clone, Debug)]
+pub struct SetupPaths {
+    prefix: PathBuf,
+}
+
+impl SetupPaths {
+    pub fn new<P: AsRef<Path>>(dir: P, num_participants: usize, num_faults: usize) -> Self {
+        Self {
+            prefix: dir
+                .as_ref()
+                .join(format!("{}_{}", num_participants, num_faults)),
+        }
+    }
+
+    pub fn from_prefix<P: Into<PathBuf>>(prefix: P) -> Self {
+        Self {
+            prefix: prefix.into(),
+        }
+    }
+
+    fn file(&self, suffix: &str) -> PathBuf {
+        let mut path = OsString::from(self.prefix.as_os_str());
+        path.push(suffix);
+        PathBuf::from(path)
+    }
+
+    pub fn cfg(&self) -> PathBuf {
+        self.file("cfg")
+    }
+
+    pub fn pks(&self) -> PathBuf {
+        self.file("pks")
+    }
+
+    pub fn cms(&self) -> PathBuf {
+        self.file("cms")
+    }
+
+    /// Committee file written by the generator for local runs.
+    pub fn committee(&self) -> PathBuf {
+        self.file("committee.json")
+    }
+
+    /// Private keystore of node `id`.
+    pub fn keystore(&self, id: usize) -> PathBuf {
+        self.file(&format!("sk{}", id))
+    }
+
+    /// Identity of node `id`, for committees generated for local runs.
+    pub fn identity(&self, id: usize) -> PathBuf {
+        self.file(&format!("id{}", id))
+    }
+}
+
+impl fmt::Display for SetupPaths {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.prefix.display())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Input<E: PairingEngine> {
     pub config: Config<E>,
     pub pks: Vec<ComGroup<E>>,
-    pub sks: Vec<EncGroup<E>>,
+    pub sk: EncGroup<E>,
+    pub a_i: Scalar<E>,
+    pub auth_sk: Scalar<E>,
+    pub auth_pks: Vec<EncGroup<E>>,
+    pub noise_sk: Vec<u8>,
+    pub noise_pks: Vec<Vec<u8>>,
+    pub commitments: Vec<Commitment<E>>,
+    pub qual: HashSet<usize>,
+}
+
+/// The public part of a setup, which is all that is needed to verify beacons.
+#[derive(Debug, Clone)]
+pub struct PublicSetup<E: PairingEngine> {
+    pub config: Config<E>,
+    pub pks: Vec<ComGroup<E>>,
     pub commitments: Vec<Commitment<E>>,
     pub qual: HashSet<usize>,
 }
@@ -37,190 +141,286 @@ pub struct Input<E: PairingEngine> {
 #[derive(CanonicalSerialize, CanonicalDeserialize, Clone, Debug)]
 pub struct Commitment<E: PairingEngine> {
     pub id: usize,
-    pub a_i: Scalar<E>,
     pub part1: ComGroup<E>,
     pub part2: EncGroup<E>,
 }
 
 unsafe impl<E: PairingEngine> Send for Commitment<E> {}
 
-#[allow(dead_code)]
-pub fn generate_setup_files<E: PairingEngine>(
-    num_participants: usize,
-    num_faults: usize,
-) {
-    let cfg_path = format!("configs/{}_{}cfg", num_participants, num_faults);
-    let pks_path = format!("configs/{}_{}pks", num_participants, num_faults);
-    let sks_path = format!("configs/{}_{}sks", num_participants, num_faults);
-    let cms_path = format!("configs/{}_{}cms", num_participants, num_faults);
-    let rng = &mut thread_rng();
-
-    // Generate new srs and config
-    let srs = SRS::<E>::setup(rng).unwrap();
-
-    // Set global configuration parameters
-    let conf = Config {
-        srs: srs.clone(),
-        degree: num_faults,
-        num_participants,
-    };
-
-    let mut conf_bytes = vec![];
-    conf.serialize(&mut conf_bytes).unwrap();
-
-    let mut conf_file = fs::File::create(&cfg_path).unwrap();
-
-    conf_file.write_all(&conf_bytes).unwrap();
+/// Private key material of a single node. Only the owning node should ever hold this file.
+#[derive(CanonicalSerialize, CanonicalDeserialize, Clone, Debug)]
+pub struct Keystore<E: PairingEngine> {
+    pub id: usize,
+    pub sk: EncGroup<E>,
+    pub a_i: Scalar<E>, // random scalar used for the node's commitment
+    pub auth_sk: Scalar<E>, // key used to authenticate to other nodes
+    pub noise_sk: Vec<u8>,  // static key of the encrypted transport
+}
 
-    let schnorr_srs = SCHSRS::<EncGroup<E>>::from_generator(conf.srs.g1).unwrap();
-    let schnorr_sig = SchnorrSignature { srs: schnorr_srs };
+/// Long-term keys of a node, generated by the node itself before the committee is formed. Only
+/// the public keys leave the host, as the node's entry in the committee file.
+#[derive(CanonicalSerialize, CanonicalDeserialize, Clone, Debug)]
+pub struct NodeIdentity<E: PairingEngine> {
+    pub auth_sk: Scalar<E>, // key used to authenticate to other nodes
+    pub noise_sk: Vec<u8>,  // static key of the encrypted transport
+    pub noise_pk: Vec<u8>,
+}
 
-    let mut dealers = vec![];
-    let mut nodes = vec![];
+impl<E: PairingEngine> NodeIdentity<E> {
+    pub fn generate<R: Rng>(rng: &mut R) -> Self {
+        let noise_keypair = snow::Builder::new(NOISE_PARAMS.parse().unwrap())
+            .generate_keypair()
+            .unwrap();
 
-    for id in 0..num_participants {
-        // Generate key pairs for party
-        let dealer_keypair_sig = schnorr_sig.generate_keypair(rng).unwrap(); // (sk, pk)
-        let eddsa_keypair = generate_production_keypair(); // (pk, sk)
-
-        // Create the dealer instance for party
-        let dealer: Dealer<E, SchnorrSignature<EncGroup<E>>> = Dealer {
-            private_key_sig: dealer_keypair_sig.0,
-            private_key_ed: eddsa_keypair.1,
-            participant: Participant {
-                pairing_type: PhantomData,
-                id,
-                public_key_sig: dealer_keypair_sig.1,
-                public_key_ed: eddsa_keypair.0,
-            },
-        };
-
-        dealers.push(dealer);
+        Self {
+            auth_sk: Scalar::<E>::rand(rng),
+            noise_sk: noise_keypair.private,
+            noise_pk: noise_keypair.public,
+        }
     }
 
-    let participants_vec = (0..num_participants)
-        .map(|i| dealers[i].participant.clone())
-        .collect::<Vec<_>>();
-
-    let mut participants = BTreeMap::new();
-    for (id, party) in (0..num_participants).zip(participants_vec) {
-        participants.insert(id, party);
+    /// Committee entry announcing the public keys of the identity. Id, host and port are
+    /// placeholders to be filled in when the committee is assembled.
+    pub fn member(&self) -> Member {
+        let auth_key = EncGroup::<E>::prime_subgroup_generator()
+            .mul(self.auth_sk)
+            .into_affine();
+
+        Member {
+            id: 0,
+            host: "127.0.0.1".to_string(),
+            port: 9000,
+            auth_key: encode_key(&auth_key),
+            noise_key: hex::encode(&self.noise_pk),
+            label: None,
+            listen: None,
+        }
     }
+}
 
-    for i in 0..num_participants {
-        // Create the node instance for party
-        let node = Node::new(
-            conf.clone(),
-            schnorr_sig.clone(),
-            dealers[i].clone(),
-            participants.clone(),
-        )
-        .unwrap();
-
-        nodes.push(node);
+/// Generates a new node identity and writes it to `path`. Returns the node's committee entry.
+pub fn generate_identity<E: Curve>(path: &Path, force: bool) -> Result<Member, SetupError> {
+    if !force && path.exists() {
+        return Err(SetupError::Exists(path.to_path_buf()));
+    }
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|e| SetupError::Io(dir.to_path_buf(), e))?;
     }
 
-    // Sample a random polynomial of degree t.
-    let f = Poly::<E>::rand(num_faults, rng);
+    let identity = NodeIdentity::<E>::generate(&mut thread_rng());
+    write_identity(path, &identity)?;
 
-    // Compute polynomial evaluations: f(1), ..., f(n).
-    let s = (1..=num_participants)
-        .map(|i| f.evaluate(&Scalar::<E>::from(i as u64)))
-        .collect::<Vec<_>>();
+    Ok(identity.member())
+}
 
-    let pvss_core = PVSSCore::<E> {
-        encs: (0..num_participants)
-            .map(|i| {
-                nodes[i]
-                    .aggregator
-                    .participants
-                    .get(&i)
-                    .ok_or(PVSSError::<E>::InvalidParticipantId(i))
-                    .unwrap()
-                    .public_key_sig
-                    .mul(s[i])
-                    .into_affine()
-            })
-            .collect::<Vec<EncGroup<E>>>(),
-        comms: (0..num_participants)
-            .map(|i| conf.srs.g2.mul(s[i]).into_affine())
-            .collect::<Vec<ComGroup<E>>>(), // PKs
+/// Reads a node identity written by `generate_identity`.
+pub fn read_identity<E: Curve>(path: &Path) -> Result<NodeIdentity<E>, SetupError> {
+    read_file::<E, NodeIdentity<E>>(path, Kind::Identity).map(|(_, identity)| identity)
+}
+
+fn write_identity<E: Curve>(path: &Path, identity: &NodeIdentity<E>) -> Result<(), SetupError> {
+    // An identity is not tied to any setup yet.
+    let header = Header {
+        curve: E::ID,
+        kind: Kind::Identity,
+        num_participants: 0,
+        num_faults: 0,
+        committee: [0; 32],
     };
 
-    // Compute "secret key shares" for all nodes
-    let sks = (0..num_participants)
-        .map(|i| {
-            DecryptedShare::<E>::generate(
-                &pvss_core.encs,
-                &nodes[i].dealer.private_key_sig,
-                nodes[i].dealer.participant.id,
-            )
-            .dec
-        })
-        .collect::<Vec<_>>();
+    let mut identity_bytes = vec![];
+    identity.serialize(&mut identity_bytes).unwrap();
+    write_atomic(path, &seal(&header, &identity_bytes), true)
+}
 
-    let mut sks_bytes = vec![];
-    sks.serialize(&mut sks_bytes).unwrap();
+/// Generates the public config (SRS and threshold) of a committee into `dir`. The key shares are
+/// then generated by the members themselves, by running the distributed key generation on it.
+pub fn generate_config<E: Curve>(
+    dir: &Path,
+    committee: &Committee<E>,
+    num_faults: usize,
+    force: bool,
+) -> Result<SetupPaths, SetupError> {
+    let num_participants = committee.members.len();
+    if num_faults >= num_participants {
+        return Err(SetupError::Invalid(format!(
+            "threshold {} requires at least {} participants",
+            num_faults,
+            num_faults + 1
+        )));
+    }
 
-    let mut sks_file = fs::File::create(&sks_path).unwrap();
+    let paths = SetupPaths::new(dir, num_participants, num_faults);
 
-    sks_file.write_all(&sks_bytes).unwrap();
+    // Refuse to overwrite an existing setup unless asked to.
+    if !force {
+        let mut targets = vec![paths.cfg(), paths.pks(), paths.cms()];
+        targets.extend((0..num_participants).map(|id| paths.keystore(id)));
+        if let Some(path) = targets.into_iter().find(|path| path.exists()) {
+            return Err(SetupError::Exists(path));
+        }
+    }
+    fs::create_dir_all(dir).map_err(|e| SetupError::Io(dir.to_path_buf(), e))?;
 
-    // Compute the shared "public key shares"
-    let pks = pvss_core.comms.clone();
+    // Generate new srs and config
+    let conf = Config {
+        srs: SRS::<E>::setup(&mut thread_rng()).unwrap(),
+        degree: num_faults,
+        num_participants,
+    };
 
-    let mut pks_bytes = vec![];
-    pks.serialize(&mut pks_bytes).unwrap();
+    let mut conf_bytes = vec![];
+    conf.serialize(&mut conf_bytes).unwrap();
 
-    let mut pks_file = fs::File::create(&pks_path).unwrap();
+    // Every file records the curve, n, t and the committee it was generated for.
+    let header = Header {
+        curve: E::ID,
+        kind: Kind::Config,
+        num_participants: num_participants as u64,
+        num_faults: num_faults as u64,
+        committee: committee.hash(),
+    };
+    write_atomic(&paths.cfg(), &seal(&header, &conf_bytes), false)?;
+
+    Ok(paths)
+}
 
-    pks_file.write_all(&pks_bytes).unwrap();
+/// Generates a committee of `num_participants` nodes on 127.0.0.1:9000 + id for local runs: one
+/// identity per node, the committee file and the config. All identities end up on this machine,
+/// so this is only meant for testing; the key shares are still generated by the nodes.
+pub fn generate_local_setup<E: Curve>(
+    dir: &Path,
+    num_participants: usize,
+    num_faults: usize,
+    parameters: Parameters,
+    force: bool,
+) -> Result<SetupPaths, SetupError> {
+    let paths = SetupPaths::new(dir, num_participants, num_faults);
+
+    if !force {
+        let mut targets = vec![paths.committee()];
+        targets.extend((0..num_participants).map(|id| paths.identity(id)));
+        if let Some(path) = targets.into_iter().find(|path| path.exists()) {
+            return Err(SetupError::Exists(path));
+        }
+    }
+    fs::create_dir_all(dir).map_err(|e| SetupError::Io(dir.to_path_buf(), e))?;
 
-    // Compute commitments vector for each party
-    let mut cms = vec![];
+    let rng = &mut thread_rng();
+    let mut members = vec![];
+    for id in 0..num_participants {
+        let identity = NodeIdentity::<E>::generate(rng);
+        write_identity(&paths.identity(id), &identity)?;
+
+        members.push(Member {
+            id,
+            port: 9000 + id as u16,
+            ..identity.member()
+        });
+    }
 
-    for i in 0..num_participants {
-        let a_i = <E as PairingEngine>::Fr::rand(rng);
+    let committee = Committee::<E>::new(CommitteeFile {
+        parameters,
+        members,
+    })
+    .map_err(|e| SetupError::Invalid(e.to_string()))?;
+    write_atomic(&paths.committee(), committee.to_json().as_bytes(), false)?;
 
-        let cm_i = Commitment::<E> {
-            id: i,
-            a_i,
-            part1: conf.srs.g2.mul(a_i).into_affine(), // into_repr()
-            part2: sks[i] + conf.srs.g1.mul(a_i).neg().into_affine(), // into_repr()
-        };
+    generate_config(dir, &committee, num_faults, force)
+}
 
-        cms.push(cm_i);
+/// Writes the result of a node's key generation: the public keys and commitments, which are the
+/// same on every node, and the node's own keystore. Only an existing keystore needs `force`, as
+/// nodes sharing a directory in local runs all write the public files.
+pub fn write_key_shares<E: Curve>(
+    paths: &SetupPaths,
+    committee: &Committee<E>,
+    pks: &[ComGroup<E>],
+    cms: &[Commitment<E>],
+    keystore: &Keystore<E>,
+    force: bool,
+) -> Result<(), SetupError> {
+    let (setup, _) = read_config(paths, committee)?;
+
+    let keystore_path = paths.keystore(keystore.id);
+    if !force && keystore_path.exists() {
+        return Err(SetupError::Exists(keystore_path));
     }
 
-    let mut cms_bytes = vec![];
+    let header = |kind| Header {
+        kind,
+        ..setup.clone()
+    };
 
+    let mut pks_bytes = vec![];
+    pks.serialize(&mut pks_bytes).unwrap();
+    let mut cms_bytes = vec![];
     cms.serialize(&mut cms_bytes).unwrap();
+    let mut keystore_bytes = vec![];
+    keystore.serialize(&mut keystore_bytes).unwrap();
+
+    write_atomic(&paths.pks(), &seal(&header(Kind::PublicKeys), &pks_bytes), false)?;
+    write_atomic(&paths.cms(), &seal(&header(Kind::Commitments), &cms_bytes), false)?;
+    write_atomic(
+        &keystore_path,
+        &seal(&header(Kind::Keystore), &keystore_bytes),
+        true,
+    )
+}
 
-    let mut cms_file = fs::File::create(&cms_path).unwrap();
+pub fn parse_files<E: Curve>(
+    id: usize,
+    paths: &SetupPaths,
+    committee: &Committee<E>,
+) -> Result<Input<E>, SetupError> {
+    let (setup, public) = read_public_files(paths, committee)?;
+
+    // Read own keystore from file
+    let keystore_path = paths.keystore(id);
+    let keystore = read_setup_file::<E, Keystore<E>>(&keystore_path, Kind::Keystore, &setup)?;
+    if keystore.id != id {
+        return Err(SetupError::Invalid(format!(
+            "keystore {} belongs to node {}",
+            keystore_path.display(),
+            keystore.id
+        )));
+    }
 
-    cms_file.write_all(&cms_bytes).unwrap();
+    Ok(Input {
+        config: public.config,
+        pks: public.pks,
+        sk: keystore.sk,
+        a_i: keystore.a_i,
+        auth_sk: keystore.auth_sk,
+        auth_pks: committee.auth_keys.clone(),
+        noise_sk: keystore.noise_sk,
+        noise_pks: committee.noise_keys.clone(),
+        commitments: public.commitments,
+        qual: public.qual,
+    })
 }
 
-#[allow(dead_code)]
-pub fn parse_files<E: PairingEngine>(num_participants: usize, num_faults: usize) -> Input<E> {
-    let cfg_path = format!("configs/{}_{}cfg", num_participants, num_faults);
-    let pks_path = format!("configs/{}_{}pks", num_participants, num_faults);
-    let sks_path = format!("configs/{}_{}sks", num_participants, num_faults);
-    let cms_path = format!("configs/{}_{}cms", num_participants, num_faults);
-
-    // Read config from file
-    let config = Config::<E>::deserialize(&*fs::read(&cfg_path).unwrap()).unwrap();
+/// Reads and checks the public setup files of a committee, without any node's keystore.
+pub fn parse_public_files<E: Curve>(
+    paths: &SetupPaths,
+    committee: &Committee<E>,
+) -> Result<PublicSetup<E>, SetupError> {
+    read_public_files(paths, committee).map(|(_, public)| public)
+}
 
-    // Read SKs from file
-    let sks = Vec::<EncGroup<E>>::deserialize(&*fs::read(&sks_path).unwrap()).unwrap();
+fn read_public_files<E: Curve>(
+    paths: &SetupPaths,
+    committee: &Committee<E>,
+) -> Result<(Header, PublicSetup<E>), SetupError> {
+    let (setup, config) = read_config(paths, committee)?;
 
     // Read PKs from file
-    let pks = Vec::<ComGroup<E>>::deserialize(&*fs::read(&pks_path).unwrap()).unwrap();
+    let pks = read_setup_file::<E, Vec<ComGroup<E>>>(&paths.pks(), Kind::PublicKeys, &setup)?;
 
-    let cms = Vec::<Commitment<E>>::deserialize(&*fs::read(&cms_path).unwrap()).unwrap();
+    let cms = read_setup_file::<E, Vec<Commitment<E>>>(&paths.cms(), Kind::Commitments, &setup)?;
 
     let mut qual = HashSet::new();
-    for i in 0..cms.len() {
+    for i in 0..cms.len().min(pks.len()) {
         let pairs = [
             (config.srs.g1.neg().into(), pks[i].into()),
             (config.srs.g1.into(), cms[i].part1.into()),
@@ -234,37 +434,197 @@ pub fn parse_files<E: PairingEngine>(num_participants: usize, num_faults: usize)
         }
     }
 
-    Input {
+    let public = PublicSetup {
         config,
         pks,
-        sks,
         commitments: cms,
         qual,
+    };
+    check_setup(&public, committee.members.len())?;
+
+    Ok((setup, public))
+}
+
+/// Reads the config of a setup and checks that it was generated for the committee. Its header
+/// determines the setup all other files must belong to.
+pub fn read_config<E: Curve>(
+    paths: &SetupPaths,
+    committee: &Committee<E>,
+) -> Result<(Header, Config<E>), SetupError> {
+    let (setup, config) = read_file::<E, Config<E>>(&paths.cfg(), Kind::Config)?;
+    if setup.committee != committee.hash() {
+        return Err(SetupError::Invalid(format!(
+            "setup {} was generated for a different committee",
+            paths
+        )));
     }
+
+    Ok((setup, config))
 }
 
-#[allow(dead_code)]
-pub fn parse_ip_file(filename: String) -> Vec<SocketAddr> {
-    let mut addresses = Vec::new();
+/// Checks that the setup files match the committee: they must be generated for the same number
+/// of participants, and the threshold must leave enough participants to reconstruct.
+pub fn check_setup<E: PairingEngine>(
+    input: &PublicSetup<E>,
+    num_participants: usize,
+) -> Result<(), SetupError> {
+    let invalid = |reason: String| Err(SetupError::Invalid(reason));
+
+    if input.config.num_participants != num_participants {
+        return invalid(format!(
+            "setup was generated for {} participants, but the committee has {}",
+            input.config.num_participants, num_participants
+        ));
+    }
+    if input.config.degree >= num_participants {
+        return invalid(format!(
+            "threshold {} requires at least {} participants",
+            input.config.degree,
+            input.config.degree + 1
+        ));
+    }
+    if input.pks.len() != num_participants || input.commitments.len() != num_participants {
+        return invalid(format!(
+            "setup has {} public keys and {} commitments for {} participants",
+            input.pks.len(),
+            input.commitments.len(),
+            num_participants
+        ));
+    }
 
-    if let Ok(lines) = read_lines(filename) {
-        for line in lines {
-            if let Ok(ip) = line {
-                addresses.push(ip.parse::<SocketAddr>().unwrap())
+    Ok(())
+}
+
+/// Looks for the setup files of a committee of the given size in `dir`. Fails if there are none,
+/// or several with different thresholds.
+pub fn find_setup(dir: &Path, num_participants: usize) -> Result<SetupPaths, SetupError> {
+    let entries = fs::read_dir(dir).map_err(|e| SetupError::Io(dir.to_path_buf(), e))?;
+
+    let mut thresholds = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let (n, t) = name.strip_suffix("cfg")?.split_once('_')?;
+            match n.parse::<usize>() {
+                Ok(n) if n == num_participants => t.parse::<usize>().ok(),
+                _ => None,
             }
-        }
+        })
+        .collect::<Vec<_>>();
+    thresholds.sort();
+
+    match thresholds.as_slice() {
+        [num_faults] => Ok(SetupPaths::new(dir, num_participants, *num_faults)),
+        [] => Err(SetupError::Invalid(format!(
+            "no setup found for {} participants in {}",
+            num_participants,
+            dir.display()
+        ))),
+        _ => Err(SetupError::Invalid(format!(
+            "several setups found for {} participants (thresholds {:?}), select one with --setup",
+            num_participants, thresholds
+        ))),
     }
+}
+
+/// Returns the identifier of the curve the setup was generated for.
+pub fn setup_curve(paths: &SetupPaths) -> Result<u8, SetupError> {
+    let path = paths.cfg();
+    let bytes = fs::read(&path).map_err(|e| SetupError::Io(path.clone(), e))?;
+    let (header, _) = unseal(&bytes).map_err(|e| SetupError::Format(path, e))?;
 
-    addresses
+    Ok(header.curve)
 }
 
-// The output is wrapped in a Result to allow matching on errors
-// Returns an Iterator to the Reader of the lines of the file.
-#[allow(dead_code)]
-fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
-where
-    P: AsRef<Path>,
-{
-    let file = File::open(filename)?;
-    Ok(io::BufReader::new(file).lines())
+/// Reads a setup file of the given kind, checks its header and deserializes the payload.
+fn read_file<E: Curve, T: CanonicalDeserialize>(
+    path: &Path,
+    kind: Kind,
+) -> Result<(Header, T), SetupError> {
+    let bytes = fs::read(path).map_err(|e| SetupError::Io(path.to_path_buf(), e))?;
+    let (header, payload) =
+        unseal(&bytes).map_err(|e| SetupError::Format(path.to_path_buf(), e))?;
+
+    if header.curve != E::ID {
+        return Err(SetupError::Invalid(format!(
+            "{} was generated for {}, expected {}",
+            path.display(),
+            curve_name(header.curve),
+            E::NAME
+        )));
+    }
+    if header.kind != kind {
+        return Err(SetupError::Invalid(format!(
+            "{} contains {:?}, expected {:?}",
+            path.display(),
+            header.kind,
+            kind
+        )));
+    }
+
+    let value =
+        T::deserialize(payload).map_err(|e| SetupError::Corrupt(path.to_path_buf(), e))?;
+    Ok((header, value))
+}
+
+/// Reads a setup file which must belong to the same setup as the config.
+fn read_setup_file<E: Curve, T: CanonicalDeserialize>(
+    path: &Path,
+    kind: Kind,
+    setup: &Header,
+) -> Result<T, SetupError> {
+    let (header, value) = read_file::<E, T>(path, kind)?;
+
+    if header.num_participants != setup.num_participants || header.num_faults != setup.num_faults
+    {
+        return Err(SetupError::Invalid(format!(
+            "{} belongs to a setup with n = {}, t = {}, expected n = {}, t = {}",
+            path.display(),
+            header.num_participants,
+            header.num_faults,
+            setup.num_participants,
+            setup.num_faults
+        )));
+    }
+    if header.committee != setup.committee {
+        return Err(SetupError::Invalid(format!(
+            "{} was generated for a different committee",
+            path.display()
+        )));
+    }
+
+    Ok(value)
+}
+
+/// Writes a file by writing a temporary file next to it and renaming it into place, so that a
+/// crash never leaves a partially written file behind. The temporary file is named after the
+/// process, since nodes sharing a directory in local runs write the same public files. Private
+/// files are only readable by their owner.
+fn write_atomic(path: &Path, bytes: &[u8], private: bool) -> Result<(), SetupError> {
+    let mut tmp = OsString::from(path.as_os_str());
+    tmp.push(format!(".{}.tmp", std::process::id()));
+    let tmp = PathBuf::from(tmp);
+
+    let write = || -> io::Result<()> {
+        let mut file = fs::File::create(&tmp)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if private {
+                file.set_permissions(fs::Permissions::from_mode(0o600))?;
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = private;
+
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    };
+
+    write().map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        SetupError::Io(path.to_path_buf(), e)
+    })
 }
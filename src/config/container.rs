@@ -0,0 +1,144 @@
+use std::fmt;
+
+use sha3::{
+    digest::{ExtendableOutput, Update, XofReader},
+    Shake256,
+};
+
+/// Magic bytes at the start of every setup file.
+const MAGIC: [u8; 4] = *b"GRLS";
+
+/// Version of the container format. Bump whenever the header or any payload layout changes.
+const VERSION: u16 = 1;
+
+const CHECKSUM_LENGTH: usize = 32;
+const HEADER_LENGTH: usize = 4 + 2 + 1 + 1 + 8 + 8 + 32 + 8;
+
+#[cfg(test)]
+#[path = "../tests/container_tests.rs"]
+mod container_tests;
+
+/// Type of the payload of a setup file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Config = 1,
+    PublicKeys = 2,
+    Commitments = 3,
+    Keystore = 4,
+    Identity = 5,
+}
+
+impl Kind {
+    fn from_u8(kind: u8) -> Option<Self> {
+        match kind {
+            1 => Some(Kind::Config),
+            2 => Some(Kind::PublicKeys),
+            3 => Some(Kind::Commitments),
+            4 => Some(Kind::Keystore),
+            5 => Some(Kind::Identity),
+            _ => None,
+        }
+    }
+}
+
+/// Describes the setup a file belongs to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub curve: u8,
+    pub kind: Kind,
+    pub num_participants: u64,
+    pub num_faults: u64,
+    pub committee: [u8; 32], // Hash of the committee's public keys
+}
+
+#[derive(Debug)]
+pub enum ContainerError {
+    Truncated,
+    BadMagic,
+    UnsupportedVersion(u16),
+    UnknownKind(u8),
+    Checksum,
+}
+
+impl fmt::Display for ContainerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContainerError::Truncated => write!(f, "file is truncated"),
+            ContainerError::BadMagic => write!(f, "not a setup file"),
+            ContainerError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported format version {} (expected {})",
+                version, VERSION
+            ),
+            ContainerError::UnknownKind(kind) => write!(f, "unknown file type {}", kind),
+            ContainerError::Checksum => write!(f, "checksum mismatch"),
+        }
+    }
+}
+
+/// Wraps a serialized payload into a setup file:
+///
+/// magic (4) | version (2) | curve (1) | kind (1) | n (8) | t (8) | committee hash (32) |
+/// payload length (8) | payload | checksum (32)
+///
+/// Integers are little-endian. The checksum covers everything before it.
+pub fn seal(header: &Header, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LENGTH + payload.len() + CHECKSUM_LENGTH);
+    bytes.extend_from_slice(&MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.push(header.curve);
+    bytes.push(header.kind as u8);
+    bytes.extend_from_slice(&header.num_participants.to_le_bytes());
+    bytes.extend_from_slice(&header.num_faults.to_le_bytes());
+    bytes.extend_from_slice(&header.committee);
+    bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(payload);
+
+    let checksum = checksum(&bytes);
+    bytes.extend_from_slice(&checksum);
+    bytes
+}
+
+/// Checks the magic bytes, version and checksum of a setup file and returns its header and
+/// payload.
+pub fn unseal(bytes: &[u8]) -> Result<(Header, &[u8]), ContainerError> {
+    if bytes.len() < 4 || bytes[..4] != MAGIC {
+        return Err(ContainerError::BadMagic);
+    }
+    if bytes.len() < HEADER_LENGTH + CHECKSUM_LENGTH {
+        return Err(ContainerError::Truncated);
+    }
+
+    let version = u16::from_le_bytes(bytes[4..6].try_into().unwrap());
+    if version != VERSION {
+        return Err(ContainerError::UnsupportedVersion(version));
+    }
+
+    let len = u64::from_le_bytes(bytes[HEADER_LENGTH - 8..HEADER_LENGTH].try_into().unwrap());
+    let end = bytes.len() - CHECKSUM_LENGTH;
+    if len != (end - HEADER_LENGTH) as u64 {
+        return Err(ContainerError::Truncated);
+    }
+    if checksum(&bytes[..end])[..] != bytes[end..] {
+        return Err(ContainerError::Checksum);
+    }
+
+    let header = Header {
+        curve: bytes[6],
+        kind: Kind::from_u8(bytes[7]).ok_or(ContainerError::UnknownKind(bytes[7]))?,
+        num_participants: u64::from_le_bytes(bytes[8..16].try_into().unwrap()),
+        num_faults: u64::from_le_bytes(bytes[16..24].try_into().unwrap()),
+        committee: bytes[24..56].try_into().unwrap(),
+    };
+
+    Ok((header, &bytes[HEADER_LENGTH..end]))
+}
+
+fn checksum(bytes: &[u8]) -> [u8; CHECKSUM_LENGTH] {
+    let mut hasher = Shake256::default();
+    hasher.update(bytes);
+    let mut reader = hasher.finalize_xof();
+    let mut checksum = [0_u8; CHECKSUM_LENGTH];
+    XofReader::read(&mut reader, &mut checksum);
+    checksum
+}
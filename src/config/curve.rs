@@ -0,0 +1,36 @@
+use ark_bls12_377::Bls12_377;
+use ark_bls12_381::Bls12_381;
+use ark_bn254::Bn254;
+use ark_ec::PairingEngine;
+
+/// A pairing engine the beacon can run on. The identifier is recorded in the setup files so
+/// that files generated for one curve are never loaded for another.
+pub trait Curve: PairingEngine {
+    const ID: u8;
+    const NAME: &'static str;
+}
+
+impl Curve for Bls12_381 {
+    const ID: u8 = 1;
+    const NAME: &'static str = "BLS12-381";
+}
+
+impl Curve for Bls12_377 {
+    const ID: u8 = 2;
+    const NAME: &'static str = "BLS12-377";
+}
+
+impl Curve for Bn254 {
+    const ID: u8 = 3;
+    const NAME: &'static str = "BN254";
+}
+
+/// Name of the curve with the given identifier, for error messages.
+pub fn curve_name(id: u8) -> &'static str {
+    match id {
+        _ if id == Bls12_381::ID => Bls12_381::NAME,
+        _ if id == Bls12_377::ID => Bls12_377::NAME,
+        _ if id == Bn254::ID => Bn254::NAME,
+        _ => "unknown curve",
+    }
+}
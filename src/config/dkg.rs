@@ -0,0 +1,272 @@
+use ark_ec::{msm::FixedBaseMSM, AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{Field, One, PrimeField, Zero};
+use ark_poly::{polynomial::UVPolynomial, Polynomial};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
+
+use rand::Rng;
+use sha3::{
+    digest::{ExtendableOutput, Update, XofReader},
+    Shake256,
+};
+use std::ops::Neg;
+
+use optrand_pvss::{
+    modified_scrape::{config::Config, poly::Polynomial as Poly, pvss::PVSSCore},
+    nizk::{
+        dleq::{srs::SRS as DLEQSRS, DLEQProof},
+        scheme::NIZKProof,
+        utils::hash::hash_to_group,
+    },
+    ComGroup, EncGroup, Scalar,
+};
+
+use super::config::Commitment;
+
+const DEALER_PERSONA: &[u8] = b"GRandLineDealer";
+const TRANSCRIPT_PERSONA: &[u8] = b"GRandLineTranscript";
+
+#[cfg(test)]
+#[path = "../tests/dkg_tests.rs"]
+mod dkg_tests;
+
+/// A single party's contribution to the distributed key generation: its secret polynomial
+/// evaluated at every participant, encrypted under the participants' public keys and committed
+/// to in the commitment group.
+///
+/// `tag` = h_id^f(0) and the DLEQ proof `pi` that it has the same exponent as the commitment
+/// g2^f(0) bind the dealing to its dealer: h_id is derived from the dealer's id, so nobody can
+/// pass off another party's secret, or a combination of them, as their own.
+#[derive(CanonicalSerialize, CanonicalDeserialize, Clone, Debug)]
+pub struct Dealing<E: PairingEngine> {
+    pub id: usize,
+    pub encs: Vec<EncGroup<E>>,
+    pub comms: Vec<ComGroup<E>>,
+    pub tag: ComGroup<E>,
+    pub pi: <DLEQProof<ComGroup<E>, ComGroup<E>> as NIZKProof>::Proof,
+}
+
+/// Fixed-base multiplication table for a generator of the commitment group, built once and
+/// shared by all multiplications with it.
+pub struct BaseTable<E: PairingEngine> {
+    window: usize,
+    table: Vec<Vec<ComGroup<E>>>,
+}
+
+impl<E: PairingEngine> BaseTable<E> {
+    /// Builds the table for `base`, sized for about `num_scalars` multiplications.
+    pub fn new(base: ComGroup<E>, num_scalars: usize) -> Self {
+        let window = FixedBaseMSM::get_mul_window_size(num_scalars);
+        let table = FixedBaseMSM::get_window_table(
+            Scalar::<E>::size_in_bits(),
+            window,
+            base.into_projective(),
+        );
+
+        Self { window, table }
+    }
+
+    /// Multiplies the base by every scalar.
+    pub fn mul(&self, scalars: &[Scalar<E>]) -> Vec<ComGroup<E>> {
+        let products = FixedBaseMSM::multi_scalar_mul::<<ComGroup<E> as AffineCurve>::Projective>(
+            Scalar::<E>::size_in_bits(),
+            self.window,
+            &self.table,
+            scalars,
+        );
+
+        ProjectiveCurve::batch_normalization_into_affine(&products)
+    }
+}
+
+/// Base h_id of the dealer tags of party `id`. `context` identifies the key generation (e.g. the
+/// committee hash), so that tags cannot be replayed in another one.
+fn dealer_base<E: PairingEngine>(context: &[u8], id: usize) -> ComGroup<E> {
+    let mut bytes = context.to_vec();
+    bytes.extend_from_slice(&(id as u64).to_le_bytes());
+
+    hash_to_group::<ComGroup<E>>(DEALER_PERSONA, &bytes)
+        .unwrap()
+        .into_affine()
+}
+
+fn dleq<E: PairingEngine>(
+    config: &Config<E>,
+    context: &[u8],
+    id: usize,
+) -> DLEQProof<ComGroup<E>, ComGroup<E>> {
+    let srs = DLEQSRS::<ComGroup<E>, ComGroup<E>> {
+        g_public_key: dealer_base::<E>(context, id),
+        h_public_key: config.srs.g2,
+    };
+
+    DLEQProof::from_srs(srs).unwrap()
+}
+
+/// Samples a fresh polynomial of degree t and deals it to all participants. `table` must be built
+/// for `config.srs.g2`.
+pub fn deal<E: PairingEngine, R: Rng>(
+    config: &Config<E>,
+    table: &BaseTable<E>,
+    public_keys: &[EncGroup<E>],
+    context: &[u8],
+    id: usize,
+    rng: &mut R,
+) -> Dealing<E> {
+    let f = Poly::<E>::rand(config.degree, rng);
+    let secret = f.evaluate(&Scalar::<E>::zero());
+
+    // Compute polynomial evaluations: f(1), ..., f(n).
+    let s = (1..=public_keys.len())
+        .map(|i| f.evaluate(&Scalar::<E>::from(i as u64)))
+        .collect::<Vec<_>>();
+
+    let dleq = dleq(config, context, id);
+    let tag = dleq.srs.g_public_key.mul(secret).into_affine();
+    let pi = dleq.prove(rng, &secret).unwrap();
+
+    Dealing {
+        id,
+        encs: public_keys
+            .iter()
+            .zip(&s)
+            .map(|(pk, s_i)| pk.mul(*s_i).into_affine())
+            .collect(),
+        comms: table.mul(&s),
+        tag,
+        pi,
+    }
+}
+
+/// Publicly verifies a dealing: every encryption must match its commitment, the commitments
+/// must lie on a polynomial of degree at most t (SCRAPE low-degree test), and the dealer must
+/// know the secret they interpolate to.
+pub fn verify_dealing<E: PairingEngine, R: Rng>(
+    config: &Config<E>,
+    public_keys: &[EncGroup<E>],
+    context: &[u8],
+    dealing: &Dealing<E>,
+    rng: &mut R,
+) -> bool {
+    let num_participants = public_keys.len();
+    if dealing.encs.len() != num_participants
+        || dealing.comms.len() != num_participants
+        || config.degree >= num_participants
+    {
+        return false;
+    }
+
+    // g2^f(0), interpolated from the commitments to f(1), ..., f(t + 1).
+    let mut secret = <ComGroup<E> as AffineCurve>::Projective::zero();
+    for i in 1..=config.degree + 1 {
+        let x_i = Scalar::<E>::from(i as u64);
+        let mut lambda = Scalar::<E>::one();
+        for j in (1..=config.degree + 1).filter(|j| *j != i) {
+            let x_j = Scalar::<E>::from(j as u64);
+            lambda *= x_j * (x_j - x_i).inverse().unwrap();
+        }
+
+        secret += &dealing.comms[i - 1].mul(lambda);
+    }
+
+    let stmnt = (dealing.tag, secret.into_affine());
+    if dleq(config, context, dealing.id)
+        .verify(&stmnt, &dealing.pi)
+        .is_err()
+    {
+        return false;
+    }
+
+    // e(enc_i, g2) = e(pk_i, comm_i)
+    for i in 0..num_participants {
+        let pairs = [
+            (dealing.encs[i].into(), config.srs.g2.neg().into()),
+            (public_keys[i].into(), dealing.comms[i].into()),
+        ];
+
+        if !<E as PairingEngine>::product_of_pairings(pairs.iter()).is_one() {
+            return false;
+        }
+    }
+
+    // Nothing to check if any polynomial of degree n - 1 is acceptable.
+    if num_participants < config.degree + 2 {
+        return true;
+    }
+
+    // Check the commitments against a random codeword of the dual code.
+    let m = Poly::<E>::rand(num_participants - config.degree - 2, rng);
+    let mut sum = <ComGroup<E> as AffineCurve>::Projective::zero();
+    for i in 1..=num_participants {
+        let x_i = Scalar::<E>::from(i as u64);
+        let mut denominator = Scalar::<E>::one();
+        for j in (1..=num_participants).filter(|j| *j != i) {
+            denominator *= x_i - Scalar::<E>::from(j as u64);
+        }
+
+        let v_i = m.evaluate(&x_i) * denominator.inverse().unwrap();
+        sum += &dealing.comms[i - 1].mul(v_i);
+    }
+
+    sum.is_zero()
+}
+
+/// Aggregates verified dealings into the encrypted shares and commitments of the group key.
+pub fn aggregate<E: PairingEngine>(
+    dealings: &[Dealing<E>],
+    num_participants: usize,
+) -> PVSSCore<E> {
+    let mut encs = vec![<EncGroup<E> as AffineCurve>::Projective::zero(); num_participants];
+    let mut comms = vec![<ComGroup<E> as AffineCurve>::Projective::zero(); num_participants];
+
+    for dealing in dealings {
+        for i in 0..num_participants {
+            encs[i].add_assign_mixed(&dealing.encs[i]);
+            comms[i].add_assign_mixed(&dealing.comms[i]);
+        }
+    }
+
+    PVSSCore {
+        encs: ProjectiveCurve::batch_normalization_into_affine(&encs),
+        comms: ProjectiveCurve::batch_normalization_into_affine(&comms),
+    }
+}
+
+/// Digest of the encryption keys and dealings a key generation was run on, in id order. Nodes
+/// compare their digests before accepting the result, which detects a dealer that sent
+/// different dealings (or a party that announced different keys) to different nodes.
+pub fn transcript_digest<E: PairingEngine>(
+    public_keys: &[EncGroup<E>],
+    dealings: &[Dealing<E>],
+) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    public_keys.serialize(&mut bytes).unwrap();
+    for dealing in dealings {
+        dealing.serialize(&mut bytes).unwrap();
+    }
+
+    digest(&bytes)
+}
+
+/// Digest of the commitments of all nodes, in id order, on top of the transcript digest. Nodes
+/// compare it in a last round, which detects a node that sent different commitments to
+/// different nodes.
+pub fn commitments_digest<E: PairingEngine>(
+    transcript: &[u8],
+    commitments: &[Commitment<E>],
+) -> Vec<u8> {
+    let mut bytes = transcript.to_vec();
+    commitments.serialize(&mut bytes).unwrap();
+
+    digest(&bytes)
+}
+
+fn digest(bytes: &[u8]) -> Vec<u8> {
+    let mut hasher = Shake256::default();
+    hasher.update(TRANSCRIPT_PERSONA);
+    hasher.update(bytes);
+
+    let mut reader = hasher.finalize_xof();
+    let mut digest = vec![0_u8; 32];
+    XofReader::read(&mut reader, &mut digest);
+    digest
+}
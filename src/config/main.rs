@@ -1,25 +1,149 @@
+use ark_bls12_377::Bls12_377;
 use ark_bls12_381::Bls12_381;
-use clap::Parser;
-use crate::config::generate_setup_files;
+use ark_bn254::Bn254;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+use grandline::config::{
+    generate_config, generate_identity, generate_local_setup, read_committee, Committee, Curve,
+    Parameters,
+};
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum CurveChoice {
+    #[value(name = "bls12-381")]
+    Bls12_381,
+    #[value(name = "bls12-377")]
+    Bls12_377,
+    #[value(name = "bn254")]
+    Bn254,
+}
 
 #[derive(Debug, Parser)]
 #[clap(author, version, about)]
 struct ConfigArgs {
-    /// Number of participants
-    num_participants: usize,
-    /// Number of faults
-    num_faults: Option<usize>,
+    /// Pairing curve to generate keys and setup for
+    #[clap(long, global = true, value_enum, default_value_t = CurveChoice::Bls12_381)]
+    curve: CurveChoice,
+    /// Overwrite existing files
+    #[clap(long, global = true)]
+    force: bool,
+    #[command(subcommand)]
+    command: Command,
 }
 
-mod config;
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Generate the identity of a node and print its committee entry. Run this on the node's
+    /// host; id, host and port of the entry are placeholders.
+    Keygen {
+        /// Path to write the identity to
+        identity: PathBuf,
+    },
+    /// Generate the config of a committee. The nodes then generate their key shares with
+    /// `app --dkg`.
+    Config {
+        /// Path to the committee file (JSON) listing all nodes
+        committee: PathBuf,
+        /// Number of faults, n / 2 - 1 by default
+        num_faults: Option<usize>,
+        /// Directory to write the setup files to
+        #[clap(long, env = "GRANDLINE_CONFIG_DIR", default_value = "configs")]
+        out_dir: PathBuf,
+    },
+    /// Generate identities, committee file and config of a committee on 127.0.0.1 for local
+    /// runs
+    Local {
+        /// Number of participants
+        num_participants: usize,
+        /// Number of faults, n / 2 - 1 by default
+        num_faults: Option<usize>,
+        /// Directory to write the setup files to
+        #[clap(long, env = "GRANDLINE_CONFIG_DIR", default_value = "configs")]
+        out_dir: PathBuf,
+        /// Encrypt the traffic between nodes (recorded in the committee file)
+        #[clap(long)]
+        encrypt: bool,
+        /// Number of epochs nodes compute and broadcast sigmas for in advance (recorded in the
+        /// committee file)
+        #[clap(long, default_value_t = 0)]
+        pipeline: u64,
+    },
+}
 
 fn main() {
     let args = ConfigArgs::parse();
 
-    let num_faults = match args.num_faults {
-        Some(faults) => faults,
-        None => (args.num_participants / 2) - 1,
+    let result = match args.curve {
+        CurveChoice::Bls12_381 => run::<Bls12_381>(args),
+        CurveChoice::Bls12_377 => run::<Bls12_377>(args),
+        CurveChoice::Bn254 => run::<Bn254>(args),
     };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run<E: Curve>(args: ConfigArgs) -> Result<(), String> {
+    match args.command {
+        Command::Keygen { identity } => {
+            let member = generate_identity::<E>(&identity, args.force)
+                .map_err(|e| format!("Failed to generate identity: {}", e))?;
+            println!("{}", serde_json::to_string_pretty(&member).unwrap());
+        }
+        Command::Config {
+            committee,
+            num_faults,
+            out_dir,
+        } => {
+            let committee = read_committee(&committee)
+                .and_then(Committee::<E>::new)
+                .map_err(|e| e.to_string())?;
+            let num_faults = faults(committee.members.len(), num_faults)?;
+
+            let paths = generate_config(&out_dir, &committee, num_faults, args.force)
+                .map_err(|e| format!("Failed to generate setup: {}", e))?;
+            println!("{}", paths);
+        }
+        Command::Local {
+            num_participants,
+            num_faults,
+            out_dir,
+            encrypt,
+            pipeline,
+        } => {
+            let num_faults = faults(num_participants, num_faults)?;
+            let parameters = Parameters { encrypt, pipeline };
+
+            let paths = generate_local_setup::<E>(
+                &out_dir,
+                num_participants,
+                num_faults,
+                parameters,
+                args.force,
+            )
+            .map_err(|e| format!("Failed to generate setup: {}", e))?;
+            println!("{}", paths);
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the number of participants and picks the threshold if none is given.
+fn faults(num_participants: usize, num_faults: Option<usize>) -> Result<usize, String> {
+    if num_participants < 2 {
+        return Err("At least 2 participants are required".to_string());
+    }
+
+    let num_faults = num_faults.unwrap_or((num_participants / 2) - 1);
+    if num_faults >= num_participants {
+        return Err(format!(
+            "Threshold {} requires at least {} participants",
+            num_faults,
+            num_faults + 1
+        ));
+    }
 
-    generate_setup_files::<Bls12_381>(args.num_participants, num_faults);
+    Ok(num_faults)
 }
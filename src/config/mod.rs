@@ -1,3 +1,10 @@
+mod committee;
 mod config;
+mod container;
+mod curve;
+mod dkg;
 
-pub use crate::config::config::*;
\ No newline at end of file
+pub use crate::config::committee::*;
+pub use crate::config::config::*;
+pub use crate::config::curve::*;
+pub use crate::config::dkg::*;
\ No newline at end of file
@@ -1,85 +1,164 @@
-use async_recursion::async_recursion;
-use log::{info, debug, trace};
-use rand::thread_rng;
-use sha3::{
-    digest::{ExtendableOutput, Update, XofReader},
-    Shake256,
-};
+use log::{info, debug, trace, warn};
 use std::{
-    collections::HashMap,
-    ops::{Mul, Neg},
+    collections::{HashMap, HashSet, VecDeque},
+    ops::Range,
+    sync::Arc,
+};
+use tokio::{
+    sync::{
+        mpsc::{channel, error::TrySendError, Receiver, Sender},
+        watch,
+    },
+    task,
+    time::{self, Duration, Instant},
 };
-use tokio::sync::mpsc::{Receiver, Sender};
-
-use crate::{config::Commitment, config::Input, message::SigmaMessage};
 
-use optrand_pvss::{
-    modified_scrape::{config::Config, poly::lagrange_interpolation_gt},
-    nizk::{
-        dleq::{srs::SRS as DLEQSRS, DLEQProof},
-        scheme::NIZKProof,
-        utils::hash::hash_to_group,
+use crate::{
+    beacon::{
+        beacon_value, compute_sigma, reconstruct_sigma, verify_batch, verify_beacon, BeaconOutput,
+        Generator, Precomputed,
     },
-    ComGroup, EncGroup, GT,
+    config::Commitment,
+    config::Input,
+    message::{Message, SigmaMessage, SyncRequest, SyncResponse},
+    network::MAX_FRAME_LENGTH,
+    node::NodeOptions,
+    store::BeaconStore,
 };
 
-use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
-use ark_ff::One;
+use optrand_pvss::{modified_scrape::config::Config, ComGroup, Scalar};
+
+use ark_ec::PairingEngine;
 use ark_serialize::CanonicalSerialize;
 
-const PERSONA: &[u8] = b"OnePiece";
-const LAMBDA: usize = 256; // main security parameter
+const SYNC_BATCH: u64 = 64; // maximum number of beacons per sync response
+const SYNC_MAX_BYTES: usize = MAX_FRAME_LENGTH - 64 * 1024; // leaves room for header and encryption
+const SYNC_TIMEOUT: Duration = Duration::from_secs(2); // before asking another peer
+const VERIFIED_CHANNEL_CAPACITY: usize = 64;
+const PENDING_SLACK: u64 = 2; // epochs past the pipeline we still hold back all sigmas for
+
+/// Outcome of verifying the beacons of a sync response on a worker thread.
+struct Synced<E: PairingEngine> {
+    peer: usize,
+    beacons: Vec<BeaconOutput<E>>, // The valid beacons, in epoch order
+    complete: bool,                // False if the peer sent an invalid beacon
+}
 
-struct Proof<E: PairingEngine> {
-    sigma: (ComGroup<E>, GT<E>),
-    pi: <DLEQProof<ComGroup<E>, ComGroup<E>> as NIZKProof>::Proof,
+/// Outcome of verifying a batch of sigma messages for a single epoch on a worker thread.
+struct Verified<E: PairingEngine> {
+    epoch: u64,
+    count: usize,
+    valid: Vec<SigmaMessage<E>>,
+    invalid: Vec<SigmaMessage<E>>,
 }
 
 pub struct Core<E: PairingEngine> {
     id: usize,
-    tx: Sender<SigmaMessage<E>>,
-    rx: Receiver<SigmaMessage<E>>,
+    tx: Sender<Message<E>>,
+    rx: Receiver<Message<E>>,
+    tx_output: Sender<BeaconOutput<E>>,
+    tx_epoch: watch::Sender<u64>, // Publishes the current epoch to the network layer
+    tx_verified: Sender<Verified<E>>,
+    rx_verified: Receiver<Verified<E>>,
+    tx_synced: Sender<Synced<E>>,
+    rx_synced: Receiver<Synced<E>>,
+    store: Option<BeaconStore<E>>,
     num_participants: usize,
     num_faults: usize,
-    config: Config<E>,
+    verify_all: bool, // Also verify sigmas beyond the t + 1 needed for reconstruction
+    pipeline: u64,    // Number of epochs past the current one we broadcast sigmas for
+    config: Arc<Config<E>>,
     _pks: Vec<ComGroup<E>>,
-    sk: EncGroup<E>,
-    commitments: Vec<Commitment<E>>,
+    sk: E::G1Prepared, // Only ever used as a pairing argument
+    a_i: Scalar<E>,
+    commitments: Arc<Vec<Commitment<E>>>,
+    precomputed: Arc<Precomputed<E>>,
+    qual: Arc<HashSet<usize>>,
     epoch: u64,
-    generators: HashMap<u64, ComGroup<E>>, // Maps epoch -> generator
-    sigmas: HashMap<u64, HashMap<usize, (ComGroup<E>, GT<E>)>>, // Maps -> epoch -> id -> sigma
+    generators: HashMap<u64, Generator<E>>, // Maps epoch -> generator
+    sigmas: HashMap<u64, HashMap<usize, SigmaMessage<E>>>, // Maps -> epoch -> id -> sigma message
+    pending: HashMap<u64, HashMap<usize, SigmaMessage<E>>>, // Same as sigmas, but not verified yet
+    ahead: HashMap<usize, SigmaMessage<E>>, // Maps id -> newest unverified sigma beyond pending
+    in_flight: HashMap<u64, usize>, // Maps epoch -> number of sigmas being verified by workers
+    missed: VecDeque<Range<u64>>, // Epochs skipped by fast-forwarding, still to be fetched
+    sync_peer: usize, // Peer the missed beacons are currently requested from
+    sync_requested: Instant, // When we last asked sync_peer
+    sync_in_flight: bool, // A sync response is being verified by a worker
+    next_sigma: u64, // First epoch we have not broadcast our sigma for
+    dropped: u64, // Number of beacon values the application did not take in time
 }
 
 impl<E: PairingEngine> Core<E> {
     pub async fn spawn(
         id: usize,
-        sender: Sender<SigmaMessage<E>>,
-        rx: Receiver<SigmaMessage<E>>,
-        num_participants: usize,
-        num_faults: usize,
+        sender: Sender<Message<E>>,
+        rx: Receiver<Message<E>>,
+        tx_output: Sender<BeaconOutput<E>>,
+        tx_epoch: watch::Sender<u64>,
         input: Input<E>,
+        options: NodeOptions<E>,
     ) {
         info!("Spawning Core...");
 
+        let store = options.store;
+
+        // Resume after the last beacon in the history, if there is one.
+        let epoch = match store.as_ref().map(|store| store.latest()) {
+            Some(Ok(Some(output))) => output.epoch + 1,
+            Some(Err(e)) => {
+                warn!("Failed to read beacon history: {}", e);
+                0
+            }
+            _ => 0,
+        };
+        if epoch > 0 {
+            info!("Resuming at epoch [{}]", epoch);
+        }
+        let _ = tx_epoch.send(epoch);
+
+        let (tx_verified, rx_verified) = channel(VERIFIED_CHANNEL_CAPACITY);
+        let (tx_synced, rx_synced) = channel(1);
+        let precomputed = Precomputed::new(&input.config, &input.commitments);
+
         Self {
             id,
             tx: sender,
             rx,
-            num_participants,
-            num_faults,
-            config: input.config,
+            tx_output,
+            tx_epoch,
+            tx_verified,
+            rx_verified,
+            tx_synced,
+            rx_synced,
+            store,
+            num_participants: input.config.num_participants,
+            num_faults: input.config.degree,
+            verify_all: options.verify_all,
+            pipeline: options.parameters.pipeline,
+            config: Arc::new(input.config),
             _pks: input.pks,
-            sk: input.sks[id],
-            commitments: input.commitments.clone(),
+            sk: input.sk.into(),
+            a_i: input.a_i,
+            commitments: Arc::new(input.commitments),
+            precomputed: Arc::new(precomputed),
+            qual: Arc::new(input.qual),
             sigmas: HashMap::new(),
-            epoch: 0,
+            pending: HashMap::new(),
+            ahead: HashMap::new(),
+            in_flight: HashMap::new(),
+            epoch,
             generators: HashMap::new(),
+            missed: VecDeque::new(),
+            sync_peer: id,
+            sync_requested: Instant::now(),
+            sync_in_flight: false,
+            next_sigma: epoch,
+            dropped: 0,
         }
         .run()
         .await;
     }
 
-    #[async_recursion]
     async fn handle_sigma(&mut self, message: SigmaMessage<E>) {
         trace!("Epoch [{}]: Received sigma from {}, epoch [{}]", self.epoch, message.id, message.epoch);
         // Return if we receive a message for a previous epoch.
@@ -89,233 +168,634 @@ impl<E: PairingEngine> Core<E> {
         }
 
         // Check if the sender is qualified.
-        if message.id >= self.num_participants {
+        if message.id >= self.num_participants || !self.qual.contains(&message.id) {
             debug!(
-                "Epoch [{}]: Received unqualified message",
-                self.epoch
+                "Epoch [{}]: Received unqualified message from {}",
+                self.epoch, message.id
             );
             return;
         }
 
-        // If message is from a node currently in a future epoch.
-        if message.epoch > self.epoch {
+        // If message is from a node currently in a future epoch, beyond what is pipelined.
+        if message.epoch > self.epoch + self.pipeline {
             debug!(
                 "Epoch [{}]: Received message from future epoch [{}]",
                 self.epoch, message.epoch
             );
         }
 
-        // Verify proof.
-        if !self.verify_proof(&message) {
-            debug!(
-                "Epoch [{}]: Received invalid proof from {} [epoch {}]",
-                self.epoch, message.id, message.epoch
-            );
+        // Ignore duplicates of an already verified sigma, and surplus sigmas once the epoch has
+        // enough verified ones.
+        if let Some(sigmas) = self.sigmas.get(&message.epoch) {
+            if sigmas.contains_key(&message.id) {
+                return;
+            }
+            if !self.verify_all && sigmas.len() >= self.num_faults + 1 {
+                trace!(
+                    "Epoch [{}]: Skipping surplus sigma from {} [epoch {}]",
+                    self.epoch, message.id, message.epoch
+                );
+                return;
+            }
+        }
+
+        // Pending messages are only kept for the pipeline and some slack. Beyond that, a sender
+        // gets a single slot, enough to notice when t + 1 nodes have moved on without us.
+        if message.epoch > self.epoch + self.pipeline + PENDING_SLACK {
+            self.hold_ahead(message);
             return;
         }
 
-        // Check consistency.
-        if !self.check_consistency(&message) {
-            debug!(
-                "Epoch [{}]: Received inconsistent proof from {}",
-                self.epoch, message.id
-            );
+        // Hold the message back until there are enough sigmas to reconstruct, then verify them
+        // in one batch.
+        let epoch = message.epoch;
+        self.pending
+            .entry(epoch)
+            .or_default()
+            .insert(message.id, message);
+        self.verify_pending(epoch);
+    }
+
+    /// Keeps the newest message of each sender beyond the pending window. As soon as the held
+    /// messages of an epoch reach the threshold, they are verified like pending ones, which lets
+    /// `handle_verified` fast-forward us.
+    fn hold_ahead(&mut self, message: SigmaMessage<E>) {
+        let epoch = message.epoch;
+        if matches!(self.ahead.get(&message.id), Some(newest) if newest.epoch >= epoch) {
+            return;
+        }
+        self.ahead.insert(message.id, message);
+
+        let ids = self
+            .ahead
+            .iter()
+            .filter(|(_, message)| message.epoch == epoch)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        let verified = self.sigmas.get(&epoch).map_or(0, |sigmas| sigmas.len());
+        let in_flight = self.in_flight.get(&epoch).copied().unwrap_or(0);
+        if verified + in_flight + ids.len() < self.num_faults + 1 {
             return;
         }
 
-        // Put the messages' sigma into the sigma hash map.
-        self.store_sigma(&message);
+        let pending = self.pending.entry(epoch).or_default();
+        for id in ids {
+            pending.insert(id, self.ahead.remove(&id).unwrap());
+        }
+        self.verify_pending(epoch);
+    }
 
-        // Try to construct a beacon value.
-        self.try_reconstruction().await;
+    /// Moves the held messages the pending window has caught up with to the pending ones, and
+    /// drops those for epochs we have left.
+    fn release_ahead(&mut self) {
+        let end = self.epoch + self.pipeline + PENDING_SLACK;
+        let ids = self
+            .ahead
+            .iter()
+            .filter(|(_, message)| message.epoch <= end)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+
+        for id in ids {
+            let message = self.ahead.remove(&id).unwrap();
+            let epoch = message.epoch;
+            if epoch < self.epoch {
+                continue;
+            }
+            self.pending.entry(epoch).or_default().insert(id, message);
+            self.verify_pending(epoch);
+        }
     }
 
-    /// Checks consistency of a received SigmaMessage with the commitments
-    /// provided by the same user during the Commitment Phase.
-    fn check_consistency(&mut self, message: &SigmaMessage<E>) -> bool {
-        let pairs = [
-            (
-                self.commitments[message.id].part2.neg().into(),
-                self.get_generator(message.epoch).into(),
-            ),
-            (self.config.srs.g1.neg().into(), message.sigma.0.into()),
-        ];
+    /// Hands the pending messages of an epoch to a worker as soon as they reach the threshold
+    /// together with the verified ones and those already being verified. The result comes back
+    /// through `handle_verified`.
+    ///
+    /// Unless all sigmas are verified, only as many messages are handed out as are missing for
+    /// reconstruction. The rest stays pending in case some of them turn out to be invalid.
+    fn verify_pending(&mut self, epoch: u64) {
+        let verified = self.sigmas.get(&epoch).map_or(0, |sigmas| sigmas.len());
+        let in_flight = self.in_flight.get(&epoch).copied().unwrap_or(0);
+        let pending = self.pending.get(&epoch).map_or(0, |pending| pending.len());
+        if pending == 0 || verified + in_flight + pending < self.num_faults + 1 {
+            return;
+        }
 
-        let prod = <E as PairingEngine>::product_of_pairings(pairs.iter());
+        let messages = if self.verify_all {
+            match self.pending.remove(&epoch) {
+                Some(pending) => pending.into_values().collect::<Vec<_>>(),
+                None => return,
+            }
+        } else {
+            let missing = (self.num_faults + 1).saturating_sub(verified + in_flight);
+            if missing == 0 {
+                return;
+            }
+            let pending = self.pending.get_mut(&epoch).unwrap();
+            let ids = pending.keys().take(missing).copied().collect::<Vec<_>>();
+            ids.iter()
+                .filter_map(|id| pending.remove(id))
+                .collect::<Vec<_>>()
+        };
+        trace!(
+            "Epoch [{}]: Verifying {} sigmas for epoch [{}]",
+            self.epoch,
+            messages.len(),
+            epoch
+        );
 
-        (message.sigma.1).mul(prod).is_one()
+        let count = messages.len();
+        *self.in_flight.entry(epoch).or_insert(0) += count;
+
+        let generator = self.get_generator(epoch).clone();
+        let config = self.config.clone();
+        let commitments = self.commitments.clone();
+        let precomputed = self.precomputed.clone();
+        let tx_verified = self.tx_verified.clone();
+        task::spawn_blocking(move || {
+            let (valid, invalid) =
+                verify_batch(&config, &commitments, &precomputed, &generator, messages);
+            let _ = tx_verified.blocking_send(Verified {
+                epoch,
+                count,
+                valid,
+                invalid,
+            });
+        });
     }
 
-    /// Returns the generator for the given epoch. If there is none, then create one.
-    fn get_generator(&mut self, epoch: u64) -> ComGroup<E> {
-        match self.generators.get_mut(&epoch) {
-            Some(generator) => return generator.clone(),
-            None => {
-                trace!("Epoch [{}]: creating generator for epoch [{}]", self.epoch, epoch);
-                let generator = hash_to_group::<ComGroup<E>>(PERSONA, &epoch.to_le_bytes())
-                    .unwrap()
-                    .into_affine();
-                self.generators.insert(epoch, generator.clone());
-                return generator;
+    /// Stores the valid sigmas of a verified batch and tries to make progress with them. Results
+    /// for epochs we have already left are dropped.
+    async fn handle_verified(&mut self, verified: Verified<E>) {
+        let epoch = verified.epoch;
+        if let Some(in_flight) = self.in_flight.get_mut(&epoch) {
+            *in_flight -= verified.count;
+            if *in_flight == 0 {
+                self.in_flight.remove(&epoch);
             }
         }
-    }
 
-    /// Given a message verify its proof.
-    /// Returns true if the proof is correct.
-    fn verify_proof(&mut self, message: &SigmaMessage<E>) -> bool {
-        let stmnt = (message.sigma.0, self.commitments[message.id].part1);
-        let srs = DLEQSRS::<ComGroup<E>, ComGroup<E>> {
-            g_public_key: self.get_generator(message.epoch),
-            h_public_key: self.config.srs.g2,
-        };
-        let dleq = DLEQProof::from_srs(srs).unwrap();
+        if epoch < self.epoch {
+            trace!(
+                "Epoch [{}]: Dropping verified sigmas for previous epoch [{}]",
+                self.epoch, epoch
+            );
+            return;
+        }
+
+        for message in verified.invalid {
+            debug!(
+                "Epoch [{}]: Received invalid sigma from {} [epoch {}]",
+                self.epoch, message.id, message.epoch
+            );
+        }
+
+        // Put the messages' sigmas into the sigma hash map.
+        let peer = verified.valid.first().map(|message| message.id);
+        for message in verified.valid {
+            self.store_sigma(&message);
+        }
+
+        // Catch up if the sender's pipeline is past ours, i.e. it has left our current epoch.
+        if let Some(peer) = peer {
+            if epoch > self.epoch + self.pipeline {
+                self.try_fast_forward(peer, epoch).await;
+            }
+        }
+
+        // Try to construct a beacon value.
+        self.try_reconstruction().await;
 
-        dleq.verify(&stmnt, &message.pi).is_ok()
+        // Some of the batch may have been invalid, so verify what arrived in the meantime.
+        if epoch >= self.epoch {
+            self.verify_pending(epoch);
+        }
+    }
+
+    /// Returns the generator for the given epoch. If there is none, then create one.
+    fn get_generator(&mut self, epoch: u64) -> &Generator<E> {
+        let current = self.epoch;
+        self.generators.entry(epoch).or_insert_with(|| {
+            trace!("Epoch [{}]: creating generator for epoch [{}]", current, epoch);
+            Generator::new(epoch)
+        })
     }
 
     /// Stores the sigma of a given message in the sigma hash map.
     fn store_sigma(&mut self, message: &SigmaMessage<E>) {
         match self.sigmas.get_mut(&message.epoch) {
             Some(sigmas) => {
-                sigmas.insert(message.id, message.sigma);
+                sigmas.insert(message.id, message.clone());
             }
             None => {
-                let mut mp = HashMap::<usize, (ComGroup<E>, GT<E>)>::new();
-                mp.insert(message.id, message.sigma);
+                let mut mp = HashMap::<usize, SigmaMessage<E>>::new();
+                mp.insert(message.id, message.clone());
                 self.sigmas.insert(message.epoch, mp);
             }
         }
     }
 
+    /// Jumps ahead once a future epoch has enough reconstruction points, e.g. after a restart left
+    /// this node behind the rest of the committee. Sigmas for `epoch` are only sent by nodes that
+    /// reached `epoch - pipeline`, so that is where we continue. The beacons of the skipped epochs
+    /// are requested from the given peer, unless a sync is already under way.
+    async fn try_fast_forward(&mut self, peer: usize, epoch: u64) {
+        let ready = match self.sigmas.get(&epoch) {
+            Some(sigmas) => sigmas.len() >= self.num_faults + 1,
+            None => false,
+        };
+
+        let target = epoch - self.pipeline;
+        if ready && target > self.epoch {
+            info!("Epoch [{}]: Fast-forwarding to epoch [{}]", self.epoch, target);
+            let syncing = !self.missed.is_empty();
+            self.add_missed(self.epoch..target);
+            self.sigmas.retain(|e, _| *e >= target);
+            self.pending.retain(|e, _| *e >= target);
+            self.generators.retain(|e, _| *e >= target);
+            self.epoch = target;
+            let _ = self.tx_epoch.send(self.epoch);
+            self.release_ahead();
+
+            if !syncing {
+                self.sync_peer = peer;
+                self.request_sync().await;
+            }
+            self.broadcast_pipeline().await;
+        }
+    }
+
+    /// Records skipped epochs. The ranges are kept in epoch order, adjacent ones are merged.
+    fn add_missed(&mut self, epochs: Range<u64>) {
+        match self.missed.back_mut() {
+            Some(last) if last.end >= epochs.start => last.end = last.end.max(epochs.end),
+            _ => self.missed.push_back(epochs),
+        }
+    }
+
+    /// Asks the current sync peer for the beacons of the first range of epochs we skipped.
+    async fn request_sync(&mut self) {
+        let missed = match self.missed.front() {
+            Some(missed) => missed.clone(),
+            None => return,
+        };
+        trace!(
+            "Epoch [{}]: Requesting beacons for epochs [{}..{}] from {}",
+            self.epoch, missed.start, missed.end, self.sync_peer
+        );
+        let request = SyncRequest {
+            epoch: missed.start,
+            end: missed.end,
+            id: self.id,
+            to: self.sync_peer,
+        };
+        self.sync_requested = Instant::now();
+        self.tx.send(Message::SyncRequest(request)).await.unwrap();
+    }
+
+    /// Moves on to the next peer if the current sync peer did not deliver within the timeout,
+    /// e.g. because it is down, keeps no history or sent invalid beacons.
+    async fn check_sync(&mut self) {
+        if self.missed.is_empty()
+            || self.sync_in_flight
+            || self.sync_requested.elapsed() < SYNC_TIMEOUT
+        {
+            return;
+        }
+
+        let previous = self.sync_peer;
+        self.sync_peer = (1..self.num_participants)
+            .map(|offset| (previous + offset) % self.num_participants)
+            .find(|peer| *peer != self.id)
+            .unwrap_or(previous);
+        debug!(
+            "Epoch [{}]: No missed beacons from {}, asking {} instead",
+            self.epoch, previous, self.sync_peer
+        );
+        self.request_sync().await;
+    }
+
+    /// Answers a sync request with the requested beacons from our history, as many as fit into a
+    /// single frame. The history is read on a worker thread. Requests addressed to other nodes
+    /// are ignored.
+    fn handle_sync_request(&mut self, request: SyncRequest) {
+        if request.to != self.id {
+            return;
+        }
+        // BTreeMap::range panics on a reversed range.
+        if request.end <= request.epoch {
+            debug!(
+                "Epoch [{}]: Ignoring empty sync request [{}..{}] from {}",
+                self.epoch, request.epoch, request.end, request.id
+            );
+            return;
+        }
+
+        let store = match self.store.as_ref() {
+            Some(store) => store,
+            None => {
+                debug!("Epoch [{}]: Cannot serve sync request without history", self.epoch);
+                return;
+            }
+        };
+
+        let end = request.end.min(request.epoch.saturating_add(SYNC_BATCH));
+        let reader = store.reader(request.epoch..end);
+        let (epoch, id, tx) = (self.epoch, self.id, self.tx.clone());
+        task::spawn_blocking(move || {
+            let mut beacons = Vec::new();
+            let mut size = 0;
+            for beacon in reader {
+                let beacon = match beacon {
+                    Ok(beacon) => beacon,
+                    Err(e) => {
+                        warn!("Epoch [{}]: Failed to read beacon history: {}", epoch, e);
+                        return;
+                    }
+                };
+                size += beacon.serialized_size();
+                if size > SYNC_MAX_BYTES {
+                    break;
+                }
+                beacons.push(beacon);
+            }
+
+            trace!(
+                "Epoch [{}]: Sending {} beacons to {}",
+                epoch,
+                beacons.len(),
+                request.id
+            );
+            let response = SyncResponse {
+                epoch,
+                id,
+                to: request.id,
+                beacons,
+            };
+            let _ = tx.blocking_send(Message::SyncResponse(response));
+        });
+    }
+
+    /// Hands the missed beacons received from a peer to a worker for verification, as far as they
+    /// continue the first range of missed epochs. The result comes back through `handle_synced`.
+    /// Responses arriving while a worker is busy are dropped, `check_sync` asks again later.
+    fn handle_sync_response(&mut self, response: SyncResponse<E>) {
+        if response.to != self.id || self.sync_in_flight {
+            return;
+        }
+        let missed = match self.missed.front() {
+            Some(missed) => missed.clone(),
+            None => return,
+        };
+
+        let mut next = missed.start;
+        let mut beacons = Vec::new();
+        for beacon in response.beacons {
+            if beacon.epoch == next && next < missed.end {
+                beacons.push(beacon);
+                next += 1;
+            }
+        }
+        if beacons.is_empty() {
+            return;
+        }
+
+        self.sync_in_flight = true;
+        let peer = response.id;
+        let config = self.config.clone();
+        let commitments = self.commitments.clone();
+        let precomputed = self.precomputed.clone();
+        let qual = self.qual.clone();
+        let tx_synced = self.tx_synced.clone();
+        task::spawn_blocking(move || {
+            let mut valid = Vec::with_capacity(beacons.len());
+            let mut complete = true;
+            for beacon in beacons {
+                if !verify_beacon(
+                    &config,
+                    &commitments,
+                    &precomputed,
+                    &qual,
+                    beacon.epoch,
+                    &beacon,
+                ) {
+                    complete = false;
+                    break;
+                }
+                valid.push(beacon);
+            }
+            let _ = tx_synced.blocking_send(Synced {
+                peer,
+                beacons: valid,
+                complete,
+            });
+        });
+    }
+
+    /// Stores and delivers verified missed beacons, in epoch order. Requests the next batch if the
+    /// peer made progress but some epochs are still missing. Otherwise `check_sync` turns to
+    /// another peer once the request times out.
+    async fn handle_synced(&mut self, synced: Synced<E>) {
+        self.sync_in_flight = false;
+        if !synced.complete {
+            debug!(
+                "Epoch [{}]: Received invalid beacon from {}",
+                self.epoch, synced.peer
+            );
+        }
+
+        let mut progress = false;
+        for beacon in synced.beacons {
+            match self.missed.front() {
+                Some(missed) if missed.start == beacon.epoch => {}
+                Some(_) => continue,
+                None => break,
+            }
+
+            self.persist(&beacon);
+            self.deliver(beacon);
+            progress = true;
+
+            if let Some(missed) = self.missed.front_mut() {
+                missed.start += 1;
+                if missed.is_empty() {
+                    self.missed.pop_front();
+                }
+            }
+        }
+
+        if progress && synced.complete && !self.missed.is_empty() {
+            self.sync_peer = synced.peer;
+            self.request_sync().await;
+        }
+    }
+
     /// Checks if we have enough reconstruction points for the current epoch. If yes we can create a
-    /// beacon value.
+    /// beacon value. With a pipeline, the following epochs may be complete already as well.
     async fn try_reconstruction(&mut self) {
-        // Check if we have enough reconstruction points.
-        match self.sigmas.get(&self.epoch) {
-            Some(sigmas) => {
-                if sigmas.len() >= self.num_faults + 1 {
-                    self.compute_beacon();
+        loop {
+            // Check if we have enough reconstruction points.
+            match self.sigmas.get(&self.epoch) {
+                Some(sigmas) if sigmas.len() >= self.num_faults + 1 => {
+                    let output = self.compute_beacon();
+                    self.persist(&output);
+                    self.deliver(output);
                     self.increase_epoch().await;
                 }
-            }
-            None => {
-                self.sigmas
-                    .insert(self.epoch, HashMap::<usize, (ComGroup<E>, GT<E>)>::new());
+                Some(_) => break,
+                None => {
+                    self.sigmas
+                        .insert(self.epoch, HashMap::<usize, SigmaMessage<E>>::new());
+                    break;
+                }
             }
         }
     }
 
     /// Computes a beacon value out of the construction points for the current epoch.
-    fn compute_beacon(&mut self) {
+    fn compute_beacon(&mut self) -> BeaconOutput<E> {
         // This call is save, if compute_beacon() will only be called by try_reconstruction() after
         // checking if sigma[&self.epoch] contains any value.
         let sigmas = &self.sigmas[&self.epoch];
 
         // Reconstruct sigma := e(g_r, SK)
         let mut evals = Vec::new();
-        let mut points = Vec::new();
-
-        for i in 0..(self.num_participants) {
-            if sigmas.contains_key(&i) {
-                evals.push(sigmas[&i].1);
-                points.push((i + 1) as u64); // indices must be in {1, ..., n}
+        let mut contributors = Vec::new();
+        let mut shares = Vec::new();
+
+        for i in (0..self.num_participants).filter(|i| self.qual.contains(i)) {
+            if let Some(message) = sigmas.get(&i) {
+                evals.push((i, message.sigma.1));
+                contributors.push(i);
+                shares.push(message.clone());
             }
         }
 
-        let sigma =
-            lagrange_interpolation_gt::<E>(&evals, &points, self.config.degree as u64).unwrap();
+        let sigma = reconstruct_sigma(&self.config, &evals).unwrap();
 
         // Generate the beacon value using sigma.
-        let mut hasher = Shake256::default();
-        let mut sigma_bytes = Vec::new();
-        let _ = sigma.serialize(&mut sigma_bytes);
-        hasher.update(&sigma_bytes[..]);
-        let mut reader = hasher.finalize_xof();
-        let mut beacon_value = [0_u8; LAMBDA >> 3];
-        XofReader::read(&mut reader, &mut beacon_value);
+        let beacon_value = beacon_value::<E>(&sigma);
 
         // Print beacon value
         info!(
             "Epoch [{}]: Beacon value: {:?}.",
             self.epoch, beacon_value,
         );
+
+        BeaconOutput {
+            epoch: self.epoch,
+            value: beacon_value,
+            sigma,
+            contributors,
+            shares,
+        }
+    }
+
+    /// Appends a finished beacon value to the beacon history, if one is configured.
+    fn persist(&mut self, output: &BeaconOutput<E>) {
+        if let Some(store) = self.store.as_mut() {
+            if let Err(e) = store.append(output) {
+                warn!("Epoch [{}]: Failed to store beacon value: {}", self.epoch, e);
+            }
+        }
+    }
+
+    /// Hands a finished beacon value to the application. The protocol never waits for it: if the
+    /// output channel is full, the value is dropped. A closed output channel is not fatal either,
+    /// the node keeps participating in the protocol.
+    fn deliver(&mut self, output: BeaconOutput<E>) {
+        match self.tx_output.try_send(output) {
+            Ok(()) => (),
+            Err(TrySendError::Full(_)) => {
+                self.dropped += 1;
+                warn!(
+                    "Epoch [{}]: Beacon output channel full, dropped {} values so far",
+                    self.epoch, self.dropped
+                );
+            }
+            Err(TrySendError::Closed(_)) => {
+                debug!("Epoch [{}]: Beacon output channel closed", self.epoch)
+            }
+        }
     }
 
     /// Deletes the no longer needed entries from the sigma hash map, computes a new epoch generator
-    /// and broadcasts the new sigma.
-    #[async_recursion]
+    /// and broadcasts the sigmas that enter the pipeline.
     async fn increase_epoch(&mut self) {
         trace!("Epoch [{}]: Increasing epoch", self.epoch);
         // Erase entry for previous epoch from sigma_map
         self.sigmas.remove(&self.epoch);
+        self.pending.remove(&self.epoch);
 
         // Erase entry for previous epoch generator
         self.generators.remove(&self.epoch);
 
         // Increment epoch counter
         self.epoch += 1;
+        let _ = self.tx_epoch.send(self.epoch);
+        self.release_ahead();
 
-        self.broadcast_sigma().await;
+        self.broadcast_pipeline().await;
+    }
+
+    /// Broadcasts our sigma for the current epoch and the next `pipeline` ones, skipping the epochs
+    /// we already did.
+    async fn broadcast_pipeline(&mut self) {
+        self.next_sigma = self.next_sigma.max(self.epoch);
+        while self.next_sigma <= self.epoch + self.pipeline {
+            self.broadcast_sigma(self.next_sigma).await;
+            self.next_sigma += 1;
+        }
     }
 
-    /// Compute and broadcast sigma for the current epoch.
-    #[async_recursion]
-    async fn broadcast_sigma(&mut self) {
+    /// Compute and broadcast sigma for the given epoch.
+    async fn broadcast_sigma(&mut self, epoch: u64) {
         // Beacon epoch phase computations
-        let proof = self.compute_sigma();
+        let msg = self.compute_sigma(epoch);
 
         // Broadcast to all participants
-        let msg = SigmaMessage {
-            epoch: self.epoch,
-            id: self.id,
-            sigma: proof.sigma,
-            pi: proof.pi,
-        };
         self.broadcast(msg.clone()).await;
         self.handle_sigma(msg).await;
     }
 
     /// Broadcast a given message to every node in the network.
-    #[async_recursion]
     async fn broadcast(&mut self, msg: SigmaMessage<E>) {
-        trace!("Epoch [{}]: Broadcasting sigma", self.epoch);
-        self.tx.send(msg).await.unwrap();
+        trace!("Epoch [{}]: Broadcasting sigma for epoch [{}]", self.epoch, msg.epoch);
+        self.tx.send(Message::Sigma(msg)).await.unwrap();
     }
 
-    /// Computes and returns a sigma for the current epoch.
-    fn compute_sigma(&mut self) -> Proof<E> {
-        trace!("Epoch [{}]: Computing sigma", self.epoch);
-        // Fetch node's random scalar used for its commitment.
-        let a_i = self.commitments[self.id].a_i;
-
-        let sigma = (
-            self.get_generator(self.epoch).mul(a_i).into_affine(),
-            <E as PairingEngine>::pairing::<EncGroup<E>, ComGroup<E>>(
-                self.sk.into(),
-                self.get_generator(self.epoch).into(),
-            ),
-        );
+    /// Computes and returns our sigma message for the given epoch.
+    fn compute_sigma(&mut self, epoch: u64) -> SigmaMessage<E> {
+        trace!("Epoch [{}]: Computing sigma for epoch [{}]", self.epoch, epoch);
+        let generator = self.get_generator(epoch).clone();
 
-        let srs = DLEQSRS::<ComGroup<E>, ComGroup<E>> {
-            g_public_key: self.get_generator(self.epoch),
-            h_public_key: self.config.srs.g2,
-        };
-
-        let dleq = DLEQProof { srs };
-        let pi = dleq.prove(&mut thread_rng(), &a_i).unwrap();
-
-        Proof { sigma, pi }
+        compute_sigma(&self.config, self.id, &self.sk, self.a_i, epoch, &generator)
     }
 
     pub async fn run(&mut self) {
-        // Broadcast initial sigma.
-        self.broadcast_sigma().await;
+        // Broadcast initial sigmas.
+        self.broadcast_pipeline().await;
+
+        let mut sync_timer = time::interval(SYNC_TIMEOUT);
 
         // Listen to incoming messages and process them. Note: self.rx is the channel where we can
-        // retrieve data from the message receiver.
-        while let Some(message) = self.rx.recv().await {
-            self.handle_sigma(message).await;
+        // retrieve data from the message receiver, self.rx_verified and self.rx_synced the ones
+        // where workers return verified sigmas and synced beacons.
+        loop {
+            tokio::select! {
+                message = self.rx.recv() => match message {
+                    Some(Message::Sigma(message)) => self.handle_sigma(message).await,
+                    Some(Message::SyncRequest(request)) => self.handle_sync_request(request),
+                    Some(Message::SyncResponse(response)) => self.handle_sync_response(response),
+                    Some(message) => {
+                        trace!("Ignoring key generation message from {}", message.id())
+                    }
+                    None => break,
+                },
+                Some(verified) = self.rx_verified.recv() => self.handle_verified(verified).await,
+                Some(synced) = self.rx_synced.recv() => self.handle_synced(synced).await,
+                _ = sync_timer.tick() => self.check_sync().await,
+            }
         }
     }
 }
@@ -0,0 +1,162 @@
+use ark_bls12_377::Bls12_377;
+use ark_bls12_381::Bls12_381;
+use ark_bn254::Bn254;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use grandline::{
+    beacon::{verify_beacon, BeaconOutput, Precomputed},
+    config::{
+        curve_name, find_setup, parse_public_files, read_committee, setup_curve, Committee,
+        CommitteeFile, Curve, SetupPaths,
+    },
+    store::BeaconStore,
+};
+
+#[derive(Debug, Parser)]
+#[clap(author, version, about = "Query and verify a beacon history file")]
+struct HistoryArgs {
+    /// Path to the beacon history file written by a node (see `app --history`)
+    history: PathBuf,
+    /// Path to the committee file (JSON) listing all nodes
+    committee: PathBuf,
+    /// Directory containing the setup files
+    #[clap(long, env = "GRANDLINE_CONFIG_DIR", default_value = "configs")]
+    config_dir: PathBuf,
+    /// Common path prefix of the setup files (e.g. configs/4_1). By default the only setup in
+    /// the config directory matching the committee size is used.
+    #[clap(long)]
+    setup: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print the beacon of an epoch
+    Get { epoch: u64 },
+    /// Print the beacon with the highest epoch
+    Latest,
+    /// Print the beacons of the epochs in [start, end)
+    Range { start: u64, end: u64 },
+    /// Verify all stored beacons against the public setup and report missing epochs
+    Verify,
+}
+
+fn main() {
+    let args = HistoryArgs::parse();
+
+    let file = match read_committee(&args.committee) {
+        Ok(file) => file,
+        Err(e) => fail(e),
+    };
+
+    let setup = match args.setup.clone() {
+        Some(prefix) => SetupPaths::from_prefix(prefix),
+        None => match find_setup(&args.config_dir, file.members.len()) {
+            Ok(setup) => setup,
+            Err(e) => fail(e),
+        },
+    };
+
+    let curve = match setup_curve(&setup) {
+        Ok(curve) => curve,
+        Err(e) => fail(format!("Failed to load setup {}: {}", setup, e)),
+    };
+    match curve {
+        _ if curve == Bls12_381::ID => run::<Bls12_381>(args, file, setup),
+        _ if curve == Bls12_377::ID => run::<Bls12_377>(args, file, setup),
+        _ if curve == Bn254::ID => run::<Bn254>(args, file, setup),
+        _ => fail(format!(
+            "Setup {} uses an unsupported curve ({})",
+            setup,
+            curve_name(curve)
+        )),
+    }
+}
+
+fn run<E: Curve>(args: HistoryArgs, file: CommitteeFile, setup: SetupPaths) {
+    let store = match BeaconStore::<E>::open(&args.history) {
+        Ok(store) => store,
+        Err(e) => fail(format!(
+            "Failed to open beacon history {}: {}",
+            args.history.display(),
+            e
+        )),
+    };
+
+    let result = match args.command {
+        Command::Get { epoch } => store.get(epoch).map(|beacon| match beacon {
+            Some(beacon) => print_beacon(&beacon),
+            None => fail(format!("No beacon stored for epoch {}", epoch)),
+        }),
+        Command::Latest => store.latest().map(|beacon| match beacon {
+            Some(beacon) => print_beacon(&beacon),
+            None => fail("The beacon history is empty"),
+        }),
+        Command::Range { start, end } => store
+            .range(start..end.max(start))
+            .map(|beacons| beacons.iter().for_each(print_beacon)),
+        Command::Verify => {
+            let committee = match Committee::<E>::new(file) {
+                Ok(committee) => committee,
+                Err(e) => fail(e),
+            };
+            let public = match parse_public_files::<E>(&setup, &committee) {
+                Ok(public) => public,
+                Err(e) => fail(format!("Failed to load setup {}: {}", setup, e)),
+            };
+            let precomputed = Precomputed::new(&public.config, &public.commitments);
+
+            store.range(..).map(|beacons| {
+                let mut failures = 0;
+                let mut next = beacons.first().map(|beacon| beacon.epoch);
+                for beacon in &beacons {
+                    if let Some(expected) = next.filter(|expected| *expected < beacon.epoch) {
+                        println!("Epochs [{}, {}) are missing", expected, beacon.epoch);
+                        failures += 1;
+                    }
+                    next = Some(beacon.epoch + 1);
+
+                    if !verify_beacon(
+                        &public.config,
+                        &public.commitments,
+                        &precomputed,
+                        &public.qual,
+                        beacon.epoch,
+                        beacon,
+                    ) {
+                        println!("Epoch [{}] is invalid", beacon.epoch);
+                        failures += 1;
+                    }
+                }
+
+                println!("Verified {} beacons, {} problems", beacons.len(), failures);
+                if failures > 0 {
+                    std::process::exit(1);
+                }
+            })
+        }
+    };
+    if let Err(e) = result {
+        fail(format!(
+            "Failed to read beacon history {}: {}",
+            args.history.display(),
+            e
+        ));
+    }
+}
+
+fn print_beacon<E: Curve>(beacon: &BeaconOutput<E>) {
+    println!(
+        "{} {} {:?}",
+        beacon.epoch,
+        hex::encode(beacon.value),
+        beacon.contributors
+    );
+}
+
+fn fail<T: std::fmt::Display>(message: T) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
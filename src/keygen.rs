@@ -0,0 +1,325 @@
+//! Distributed key generation, run by every member of a committee over the beacon's network
+//! before the beacon starts (`app --dkg`).
+//!
+//! 1. Every node announces a fresh encryption key on `srs.g1`, which the others encrypt its
+//!    shares under.
+//! 2. Every node deals a random polynomial to all nodes and broadcasts the dealing. Dealings are
+//!    bound to their dealer by a proof of knowledge of the dealt secret (see `Dealing`).
+//! 3. Once it has all dealings, a node verifies them, aggregates them, decrypts its own share,
+//!    and broadcasts a confirmation with the digest of everything it aggregated and the
+//!    commitment to its share.
+//! 4. Once it has all confirmations and their digests match its own, a node broadcasts the
+//!    digest of the transcript and all commitments.
+//! 5. Once all nodes agree on that digest, the node is done.
+//!
+//! Every member must take part: an invalid dealing or a diverging digest aborts the key
+//! generation, which then has to be rerun without the offending member.
+
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::UniformRand;
+use ark_serialize::CanonicalSerialize;
+use log::{debug, info};
+use rand::thread_rng;
+use rayon::prelude::*;
+use std::{fmt, net::SocketAddr, ops::Neg};
+use tokio::{
+    sync::mpsc::Receiver,
+    task,
+    time::{sleep, Duration},
+};
+
+use optrand_pvss::{
+    modified_scrape::{config::Config, decryption::DecryptedShare},
+    signature::{scheme::SignatureScheme, schnorr::srs::SRS as SCHSRS, schnorr::SchnorrSignature},
+    ComGroup, EncGroup, Scalar,
+};
+
+use crate::{
+    config::{
+        aggregate, commitments_digest, deal, transcript_digest, verify_dealing, BaseTable,
+        Commitment, Committee, Dealing, Keystore, NodeIdentity,
+    },
+    message::{Agreement, Confirmation, EncryptionKey, Message},
+    network::{Identity, RetransmitPolicy},
+    node::spawn_network,
+};
+
+/// How long a node keeps its network running after it is done, so that the retransmitter can
+/// still deliver its agreement to nodes that are not done yet.
+#[cfg(not(test))]
+const LINGER: Duration = Duration::from_secs(10);
+#[cfg(test)]
+const LINGER: Duration = Duration::from_secs(1);
+
+#[cfg(test)]
+#[path = "tests/keygen_tests.rs"]
+mod keygen_tests;
+
+#[derive(Debug)]
+pub enum KeygenError {
+    Closed,
+    InvalidDealings(Vec<usize>),
+    InvalidCommitment(usize),
+    Diverged(Vec<usize>),
+}
+
+impl fmt::Display for KeygenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeygenError::Closed => write!(f, "network closed"),
+            KeygenError::InvalidDealings(ids) => write!(f, "invalid dealings from {:?}", ids),
+            KeygenError::InvalidCommitment(id) => {
+                write!(f, "node {} confirmed with a commitment of another node", id)
+            }
+            KeygenError::Diverged(ids) => write!(
+                f,
+                "nodes {:?} ended up with a different transcript, some node equivocated",
+                ids
+            ),
+        }
+    }
+}
+
+/// The result of the key generation at one node.
+pub struct KeygenOutput<E: PairingEngine> {
+    pub pks: Vec<ComGroup<E>>,
+    pub commitments: Vec<Commitment<E>>,
+    pub keystore: Keystore<E>,
+}
+
+/// Messages of all nodes received so far. Only the first message of each kind from a node
+/// counts; a node sending different ones to different nodes is caught by the digests.
+struct Transcript<E: PairingEngine> {
+    keys: Vec<Option<EncGroup<E>>>,
+    dealings: Vec<Option<Dealing<E>>>,
+    confirmations: Vec<Option<Confirmation<E>>>,
+    agreements: Vec<Option<Vec<u8>>>,
+}
+
+impl<E: PairingEngine> Transcript<E> {
+    fn new(num_participants: usize) -> Self {
+        Self {
+            keys: vec![None; num_participants],
+            dealings: vec![None; num_participants],
+            confirmations: vec![None; num_participants],
+            agreements: vec![None; num_participants],
+        }
+    }
+
+    fn add(&mut self, message: Message<E>) {
+        let id = message.id();
+        let added = match message {
+            Message::EncryptionKey(key) => fill(&mut self.keys, id, key.key),
+            Message::Dealing(dealing) => fill(&mut self.dealings, id, dealing),
+            Message::Confirmation(confirmation) => fill(&mut self.confirmations, id, confirmation),
+            Message::Agreement(agreement) => fill(&mut self.agreements, id, agreement.digest),
+            _ => false,
+        };
+
+        if !added {
+            debug!("Ignoring message from {}", id);
+        }
+    }
+
+    /// Waits until `done` holds, filing every message that arrives in the meantime.
+    async fn receive<F: Fn(&Self) -> bool>(
+        &mut self,
+        rx: &mut Receiver<Message<E>>,
+        done: F,
+    ) -> Result<(), KeygenError> {
+        while !done(self) {
+            let message = rx.recv().await.ok_or(KeygenError::Closed)?;
+            self.add(message);
+        }
+
+        Ok(())
+    }
+}
+
+fn fill<T>(slots: &mut [Option<T>], id: usize, value: T) -> bool {
+    match slots.get_mut(id) {
+        Some(slot @ None) => {
+            *slot = Some(value);
+            true
+        }
+        _ => false,
+    }
+}
+
+fn is_complete<T>(slots: &[Option<T>]) -> bool {
+    slots.iter().all(Option::is_some)
+}
+
+fn complete<T: Clone>(slots: &[Option<T>]) -> Vec<T> {
+    slots.iter().flatten().cloned().collect()
+}
+
+/// Fails with the ids of the nodes whose digest differs from the own one.
+fn check_digests(digests: &[Vec<u8>], digest: &[u8]) -> Result<(), KeygenError> {
+    let diverged = digests
+        .iter()
+        .enumerate()
+        .filter(|(_, other)| other.as_slice() != digest)
+        .map(|(id, _)| id)
+        .collect::<Vec<_>>();
+
+    if diverged.is_empty() {
+        Ok(())
+    } else {
+        Err(KeygenError::Diverged(diverged))
+    }
+}
+
+/// Runs the key generation as node `id` of the committee, on the given config. Returns once all
+/// nodes confirmed the same result.
+pub async fn run<E: PairingEngine>(
+    id: usize,
+    config: &Config<E>,
+    committee: &Committee<E>,
+    nodes: Vec<SocketAddr>,
+    listen_address: SocketAddr,
+    identity: NodeIdentity<E>,
+    policy: RetransmitPolicy,
+) -> Result<KeygenOutput<E>, KeygenError> {
+    let num_participants = committee.members.len();
+
+    // Dealer tags are bound to the committee and the config.
+    let mut context = committee.hash().to_vec();
+    config.serialize(&mut context).unwrap();
+
+    // Commitments are multiples of g2: one per share of the own dealing, and the node's
+    // commitment.
+    let table = BaseTable::new(config.srs.g2, num_participants + 1);
+
+    let network_identity = Identity {
+        id,
+        secret: identity.auth_sk,
+        public_keys: committee.auth_keys.clone(),
+        encrypt: committee.parameters.encrypt,
+        noise_secret: identity.noise_sk.clone(),
+        noise_public_keys: committee.noise_keys.clone(),
+    };
+    let (tx, mut rx, _tx_epoch) =
+        spawn_network(&nodes, listen_address, network_identity, policy).await;
+    let mut transcript = Transcript::<E>::new(num_participants);
+
+    // Announce the key the own shares are encrypted under.
+    let schnorr_srs = SCHSRS::<EncGroup<E>>::from_generator(config.srs.g1).unwrap();
+    let schnorr_sig = SchnorrSignature { srs: schnorr_srs };
+    let (decryption_key, encryption_key) = schnorr_sig.generate_keypair(&mut thread_rng()).unwrap();
+
+    let message = Message::EncryptionKey(EncryptionKey {
+        id,
+        key: encryption_key,
+    });
+    transcript.add(message.clone());
+    tx.send(message).await.map_err(|_| KeygenError::Closed)?;
+
+    info!(
+        "Waiting for the encryption keys of {} nodes",
+        num_participants
+    );
+    transcript
+        .receive(&mut rx, |transcript| is_complete(&transcript.keys))
+        .await?;
+    let keys = complete(&transcript.keys);
+
+    // Deal the own secret.
+    let dealing = deal(config, &table, &keys, &context, id, &mut thread_rng());
+    let message = Message::Dealing(dealing);
+    transcript.add(message.clone());
+    tx.send(message).await.map_err(|_| KeygenError::Closed)?;
+
+    info!("Waiting for the dealings of {} nodes", num_participants);
+    transcript
+        .receive(&mut rx, |transcript| is_complete(&transcript.dealings))
+        .await?;
+    let dealings = complete(&transcript.dealings);
+
+    // Verification is expensive, so it is spread over the rayon thread pool without blocking the
+    // runtime.
+    let invalid = task::block_in_place(|| {
+        dealings
+            .par_iter()
+            .filter(|dealing| !verify_dealing(config, &keys, &context, dealing, &mut thread_rng()))
+            .map(|dealing| dealing.id)
+            .collect::<Vec<_>>()
+    });
+    if !invalid.is_empty() {
+        return Err(KeygenError::InvalidDealings(invalid));
+    }
+
+    // Decrypt the own share of the group key and commit to it.
+    let core = aggregate(&dealings, num_participants);
+    let sk = DecryptedShare::<E>::generate(&core.encs, &decryption_key, id).dec;
+    let a_i = Scalar::<E>::rand(&mut thread_rng());
+    let commitment = Commitment {
+        id,
+        part1: table.mul(&[a_i])[0],
+        part2: sk + config.srs.g1.mul(a_i).neg().into_affine(),
+    };
+
+    let digest = transcript_digest(&keys, &dealings);
+    let message = Message::Confirmation(Confirmation {
+        id,
+        digest: digest.clone(),
+        commitment,
+    });
+    transcript.add(message.clone());
+    tx.send(message).await.map_err(|_| KeygenError::Closed)?;
+
+    info!(
+        "Waiting for the confirmations of {} nodes",
+        num_participants
+    );
+    transcript
+        .receive(&mut rx, |transcript| is_complete(&transcript.confirmations))
+        .await?;
+    let confirmations = complete(&transcript.confirmations);
+
+    let digests = confirmations
+        .iter()
+        .map(|confirmation| confirmation.digest.clone())
+        .collect::<Vec<_>>();
+    check_digests(&digests, &digest)?;
+    if let Some(confirmation) = confirmations
+        .iter()
+        .find(|confirmation| confirmation.commitment.id != confirmation.id)
+    {
+        return Err(KeygenError::InvalidCommitment(confirmation.id));
+    }
+    let commitments = confirmations
+        .into_iter()
+        .map(|confirmation| confirmation.commitment)
+        .collect::<Vec<_>>();
+
+    // Agree on the commitments, which are not covered by the transcript digest.
+    let digest = commitments_digest(&digest, &commitments);
+    let message = Message::Agreement(Agreement {
+        id,
+        digest: digest.clone(),
+    });
+    transcript.add(message.clone());
+    tx.send(message).await.map_err(|_| KeygenError::Closed)?;
+
+    info!("Waiting for the agreement of {} nodes", num_participants);
+    transcript
+        .receive(&mut rx, |transcript| is_complete(&transcript.agreements))
+        .await?;
+    check_digests(&complete(&transcript.agreements), &digest)?;
+
+    info!("Key generation done, waiting for the other nodes to finish");
+    sleep(LINGER).await;
+
+    Ok(KeygenOutput {
+        pks: core.comms,
+        commitments,
+        keystore: Keystore {
+            id,
+            sk,
+            a_i,
+            auth_sk: identity.auth_sk,
+            noise_sk: identity.noise_sk,
+        },
+    })
+}
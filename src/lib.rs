@@ -0,0 +1,19 @@
+//! GRandLine distributed randomness beacon.
+//!
+//! The `app` and `generator` binaries are thin wrappers around this crate. It is also what
+//! external tools link against to start a node, read a beacon history or verify beacons.
+
+pub mod beacon;
+pub mod config;
+mod core;
+pub mod keygen;
+pub mod message;
+pub mod network;
+pub mod node;
+pub mod store;
+#[cfg(any(test, feature = "test-support"))]
+#[doc(hidden)]
+pub mod test_support;
+
+pub use crate::beacon::{verify_beacon, BeaconOutput};
+pub use crate::store::BeaconStore;
@@ -1,34 +1,78 @@
+use ark_bls12_377::Bls12_377;
 use ark_bls12_381::Bls12_381;
+use ark_bn254::Bn254;
 use clap::Parser;
-use config::parse_ip_file;
-use log::debug;
+use log::{debug, error, info, warn};
+use std::{net::SocketAddr, path::PathBuf};
+use tokio::{sync::mpsc::channel, time::Duration};
 
-use crate::config::parse_files;
-
-mod config;
-mod core;
-mod message;
-mod network;
-mod node;
+use grandline::{
+    config::{
+        curve_name, find_setup, parse_files, read_committee, read_config, read_identity,
+        setup_curve, write_key_shares, Committee, CommitteeFile, Curve, SetupPaths,
+    },
+    keygen,
+    network::RetransmitPolicy,
+    node::{self, NodeOptions},
+    store::BeaconStore,
+};
 
 #[derive(Debug, Parser)]
 #[clap(author, version, about)]
 struct AppArgs {
     /// Id of the node
     node_id: usize,
-    /// Path to the file containing all IPs
-    nodes: String,
+    /// Path to the committee file (JSON) listing all nodes
+    committee: String,
     /// Level of logging
     log_level: usize,
+    /// Directory containing the setup files
+    #[clap(long, env = "GRANDLINE_CONFIG_DIR", default_value = "configs")]
+    config_dir: PathBuf,
+    /// Common path prefix of the setup files (e.g. configs/4_1). By default the only setup in
+    /// the config directory matching the committee size is used.
+    #[clap(long)]
+    setup: Option<PathBuf>,
+    /// Path to the file storing the beacon history, also used to resume after a restart
+    #[clap(long)]
+    history: Option<PathBuf>,
+    /// Address to listen on, if it differs from the node's entry in the committee file (e.g. behind
+    /// NAT or a container port mapping). Defaults to the entry's listen address, or all interfaces
+    /// on the advertised port.
+    #[clap(long)]
+    listen: Option<SocketAddr>,
+    /// Delay before the first retransmission to an unreachable peer, in milliseconds
+    #[clap(long, default_value_t = 100)]
+    retry_initial_ms: u64,
+    /// Maximum delay between retransmissions, in milliseconds
+    #[clap(long, default_value_t = 5_000)]
+    retry_max_ms: u64,
+    /// Number of consecutive failed retransmissions after which a peer is considered down
+    #[clap(long, default_value_t = 5)]
+    breaker_threshold: u32,
+    /// Delay between probes to a peer that is considered down, in milliseconds
+    #[clap(long, default_value_t = 10_000)]
+    breaker_cooldown_ms: u64,
+    /// Verify every sigma that arrives, instead of only as many as are needed for the beacon
+    #[clap(long)]
+    verify_all: bool,
+    /// Generate the node's key share together with the other nodes, instead of running the
+    /// beacon. Needs the config of the setup (see `generator config`).
+    #[clap(long)]
+    dkg: bool,
+    /// Path to the node's identity (see `generator keygen`). Defaults to the identity the
+    /// generator writes next to the setup for local runs.
+    #[clap(long)]
+    identity: Option<PathBuf>,
+    /// Overwrite an existing keystore with the one generated by --dkg
+    #[clap(long)]
+    force: bool,
 }
 
 #[tokio::main]
 async fn main() {
     let args = AppArgs::parse();
 
-    // Parse ip file
-    let addresses = parse_ip_file(args.nodes);
-
     let log_level = match args.log_level {
         1 => log::LevelFilter::Info,
         2 => log::LevelFilter::Debug,
@@ -42,13 +86,235 @@ async fn main() {
         .format_timestamp_millis()
         .init();
 
-    debug!("Addresses: {:?}", addresses);
+    // Read the committee
+    let file = match read_committee(&args.committee) {
+        Ok(file) => file,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Find the setup files for the committee
+    let setup = match args.setup.clone() {
+        Some(prefix) => SetupPaths::from_prefix(prefix),
+        None => match find_setup(&args.config_dir, file.members.len()) {
+            Ok(setup) => setup,
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+        },
+    };
+
+    // Run on the curve the setup was generated for
+    let curve = match setup_curve(&setup) {
+        Ok(curve) => curve,
+        Err(e) => {
+            error!("Failed to load setup {}: {}", setup, e);
+            std::process::exit(1);
+        }
+    };
+    info!("Using curve {}", curve_name(curve));
+    match curve {
+        _ if curve == Bls12_381::ID => run::<Bls12_381>(args, file, setup).await,
+        _ if curve == Bls12_377::ID => run::<Bls12_377>(args, file, setup).await,
+        _ if curve == Bn254::ID => run::<Bn254>(args, file, setup).await,
+        _ => {
+            error!(
+                "Setup {} uses an unsupported curve ({})",
+                setup,
+                curve_name(curve)
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+async fn run<E: Curve>(args: AppArgs, file: CommitteeFile, setup: SetupPaths) {
+    // Validate the committee and resolve the addresses of its members
+    let committee = match Committee::<E>::new(file) {
+        Ok(committee) => committee,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let addresses = match committee.resolve() {
+        Ok(addresses) => addresses,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    for (member, address) in committee.members.iter().zip(&addresses) {
+        debug!("Member {}: {}:{} -> {}", member.name(), member.host, member.port, address);
+    }
+
+    // The node's own entry is the address advertised to the other nodes.
+    let member = match committee.members.get(args.node_id) {
+        Some(member) => member,
+        None => {
+            error!(
+                "Node {} is not part of the committee ({} members)",
+                args.node_id,
+                committee.members.len()
+            );
+            std::process::exit(1);
+        }
+    };
+    let advertised_address = addresses[args.node_id];
+    let listen_address = args
+        .listen
+        .or(member.listen)
+        .unwrap_or_else(|| node::default_listen_address(advertised_address));
+    info!(
+        "Listening on {}, advertised as {}",
+        listen_address, advertised_address
+    );
+
+    let num_participants = addresses.len(); // number of participants in the network
+
+    let policy = RetransmitPolicy {
+        initial_delay: Duration::from_millis(args.retry_initial_ms),
+        max_delay: Duration::from_millis(args.retry_max_ms),
+        breaker_threshold: args.breaker_threshold,
+        breaker_cooldown: Duration::from_millis(args.breaker_cooldown_ms),
+    };
+
+    if args.dkg {
+        return generate_keys(args, &committee, &setup, addresses, listen_address, policy).await;
+    }
+
+    // Load the setup files and take the threshold from the generated config
+    let input = match parse_files::<E>(args.node_id, &setup, &committee) {
+        Ok(input) => input,
+        Err(e) => {
+            error!("Failed to load setup {}: {}", setup, e);
+            std::process::exit(1);
+        }
+    };
+    let num_faults = input.config.degree;
+    info!("Using setup {} (n = {}, t = {})", setup, num_participants, num_faults);
 
-    let num_participants = addresses.len();      // number of participants in the network
-    let num_faults = (num_participants / 2) - 1; // assume maximum number of faults (i.e., floor(num_participants/2) - 1)
+    // Report the qualified set and refuse to start if it cannot reach the threshold.
+    let mut qual = input.qual.iter().collect::<Vec<_>>();
+    qual.sort();
+    info!("Qualified participants ({}/{}): {:?}", qual.len(), num_participants, qual);
+    if !input.qual.contains(&args.node_id) {
+        warn!("Node {} is not qualified", args.node_id);
+    }
+    if input.qual.len() < num_faults + 1 {
+        error!(
+            "Only {} qualified participants, at least {} are required",
+            input.qual.len(),
+            num_faults + 1
+        );
+        std::process::exit(1);
+    }
 
-    let input = parse_files::<Bls12_381>(num_participants, num_faults);
+    // Open beacon history
+    let store = match args.history {
+        Some(path) => match BeaconStore::open(&path) {
+            Ok(store) => Some(store),
+            Err(e) => {
+                error!("Failed to open beacon history {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    // Consume beacon values delivered by the node.
+    let (tx_output, mut rx_output) = channel(1_000);
+    tokio::spawn(async move {
+        while let Some(output) = rx_output.recv().await {
+            debug!(
+                "Epoch [{}]: Beacon output from contributors {:?}",
+                output.epoch, output.contributors
+            );
+        }
+    });
 
     // Spawn node
-    node::new(args.node_id, addresses, num_participants, num_faults, input).await;
+    let options = NodeOptions {
+        store,
+        parameters: committee.parameters,
+        policy,
+        verify_all: args.verify_all,
+    };
+    node::new(args.node_id, addresses, listen_address, tx_output, input, options).await;
+}
+
+/// Runs the distributed key generation with the other nodes and writes the node's key share.
+async fn generate_keys<E: Curve>(
+    args: AppArgs,
+    committee: &Committee<E>,
+    setup: &SetupPaths,
+    addresses: Vec<SocketAddr>,
+    listen_address: SocketAddr,
+    policy: RetransmitPolicy,
+) {
+    let identity_path = args
+        .identity
+        .unwrap_or_else(|| setup.identity(args.node_id));
+    let identity = match read_identity::<E>(&identity_path) {
+        Ok(identity) => identity,
+        Err(e) => {
+            error!("Failed to load identity: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let config = match read_config(setup, committee) {
+        Ok((_, config)) => config,
+        Err(e) => {
+            error!("Failed to load setup {}: {}", setup, e);
+            std::process::exit(1);
+        }
+    };
+
+    // Fail before the other nodes wait for this one, not after.
+    let keystore = setup.keystore(args.node_id);
+    if !args.force && keystore.exists() {
+        error!(
+            "{} already exists, use --force to overwrite it",
+            keystore.display()
+        );
+        std::process::exit(1);
+    }
+
+    info!(
+        "Generating keys for setup {} (n = {}, t = {})",
+        setup, config.num_participants, config.degree
+    );
+    let output = match keygen::run(
+        args.node_id,
+        &config,
+        committee,
+        addresses,
+        listen_address,
+        identity,
+        policy,
+    )
+    .await
+    {
+        Ok(output) => output,
+        Err(e) => {
+            error!("Key generation failed: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(e) = write_key_shares(
+        setup,
+        committee,
+        &output.pks,
+        &output.commitments,
+        &output.keystore,
+        args.force,
+    ) {
+        error!("Failed to write key shares: {}", e);
+        std::process::exit(1);
+    }
+    info!("Wrote key shares of setup {}", setup);
 }
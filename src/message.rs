@@ -3,7 +3,12 @@ use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, Serializatio
 
 use optrand_pvss::{
     nizk::{dleq::DLEQProof, scheme::NIZKProof},
-    ComGroup, GT,
+    ComGroup, EncGroup, GT,
+};
+
+use crate::{
+    beacon::BeaconOutput,
+    config::{Commitment, Dealing},
 };
 
 #[derive(CanonicalSerialize, CanonicalDeserialize, Debug, Clone)]
@@ -13,3 +18,158 @@ pub struct SigmaMessage<E: PairingEngine> {
     pub sigma: (ComGroup<E>, GT<E>),
     pub pi: <DLEQProof<ComGroup<E>, ComGroup<E>> as NIZKProof>::Proof,
 }
+
+/// Asks node `to` for the finished beacons of epochs `epoch..end`.
+#[derive(CanonicalSerialize, CanonicalDeserialize, Debug, Clone)]
+pub struct SyncRequest {
+    pub epoch: u64,
+    pub end: u64,
+    pub id: usize,
+    pub to: usize,
+}
+
+/// Finished beacons (including their proof material) sent to a lagging node `to`. `epoch` is the
+/// current epoch of the responding node.
+#[derive(CanonicalSerialize, CanonicalDeserialize, Debug, Clone)]
+pub struct SyncResponse<E: PairingEngine> {
+    pub epoch: u64,
+    pub id: usize,
+    pub to: usize,
+    pub beacons: Vec<BeaconOutput<E>>,
+}
+
+/// Public key the shares dealt to node `id` are encrypted under during key generation.
+#[derive(CanonicalSerialize, CanonicalDeserialize, Debug, Clone)]
+pub struct EncryptionKey<E: PairingEngine> {
+    pub id: usize,
+    pub key: EncGroup<E>,
+}
+
+/// Sent by node `id` once it has aggregated all dealings of the key generation: the digest of
+/// the keys and dealings it used, and the commitment to its own share.
+#[derive(CanonicalSerialize, CanonicalDeserialize, Debug, Clone)]
+pub struct Confirmation<E: PairingEngine> {
+    pub id: usize,
+    pub digest: Vec<u8>,
+    pub commitment: Commitment<E>,
+}
+
+/// Sent by node `id` once it has the confirmations of all nodes: the digest of the transcript
+/// and all commitments.
+#[derive(CanonicalSerialize, CanonicalDeserialize, Debug, Clone)]
+pub struct Agreement {
+    pub id: usize,
+    pub digest: Vec<u8>,
+}
+
+/// Everything that is sent between nodes.
+#[derive(Debug, Clone)]
+pub enum Message<E: PairingEngine> {
+    Sigma(SigmaMessage<E>),
+    SyncRequest(SyncRequest),
+    SyncResponse(SyncResponse<E>),
+    EncryptionKey(EncryptionKey<E>),
+    Dealing(Dealing<E>),
+    Confirmation(Confirmation<E>),
+    Agreement(Agreement),
+}
+
+impl<E: PairingEngine> Message<E> {
+    /// Id of the sending node.
+    pub fn id(&self) -> usize {
+        match self {
+            Message::Sigma(message) => message.id,
+            Message::SyncRequest(request) => request.id,
+            Message::SyncResponse(response) => response.id,
+            Message::EncryptionKey(key) => key.id,
+            Message::Dealing(dealing) => dealing.id,
+            Message::Confirmation(confirmation) => confirmation.id,
+            Message::Agreement(agreement) => agreement.id,
+        }
+    }
+
+    /// Epoch the message refers to. Key generation happens before the first epoch.
+    pub fn epoch(&self) -> u64 {
+        match self {
+            Message::Sigma(message) => message.epoch,
+            Message::SyncRequest(request) => request.epoch,
+            Message::SyncResponse(response) => response.epoch,
+            Message::EncryptionKey(_)
+            | Message::Dealing(_)
+            | Message::Confirmation(_)
+            | Message::Agreement(_) => 0,
+        }
+    }
+
+    /// Id of the node the message is addressed to, or `None` if it is meant for all nodes.
+    pub fn recipient(&self) -> Option<usize> {
+        match self {
+            Message::SyncRequest(request) => Some(request.to),
+            Message::SyncResponse(response) => Some(response.to),
+            _ => None,
+        }
+    }
+}
+
+// Messages are encoded as a one byte tag followed by the variant.
+impl<E: PairingEngine> CanonicalSerialize for Message<E> {
+    fn serialize<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
+        match self {
+            Message::Sigma(message) => {
+                0u8.serialize(&mut writer)?;
+                message.serialize(&mut writer)
+            }
+            Message::SyncRequest(request) => {
+                1u8.serialize(&mut writer)?;
+                request.serialize(&mut writer)
+            }
+            Message::SyncResponse(response) => {
+                2u8.serialize(&mut writer)?;
+                response.serialize(&mut writer)
+            }
+            Message::EncryptionKey(key) => {
+                3u8.serialize(&mut writer)?;
+                key.serialize(&mut writer)
+            }
+            Message::Dealing(dealing) => {
+                4u8.serialize(&mut writer)?;
+                dealing.serialize(&mut writer)
+            }
+            Message::Confirmation(confirmation) => {
+                5u8.serialize(&mut writer)?;
+                confirmation.serialize(&mut writer)
+            }
+            Message::Agreement(agreement) => {
+                6u8.serialize(&mut writer)?;
+                agreement.serialize(&mut writer)
+            }
+        }
+    }
+
+    fn serialized_size(&self) -> usize {
+        1 + match self {
+            Message::Sigma(message) => message.serialized_size(),
+            Message::SyncRequest(request) => request.serialized_size(),
+            Message::SyncResponse(response) => response.serialized_size(),
+            Message::EncryptionKey(key) => key.serialized_size(),
+            Message::Dealing(dealing) => dealing.serialized_size(),
+            Message::Confirmation(confirmation) => confirmation.serialized_size(),
+            Message::Agreement(agreement) => agreement.serialized_size(),
+        }
+    }
+}
+
+impl<E: PairingEngine> CanonicalDeserialize for Message<E> {
+    fn deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+        match u8::deserialize(&mut reader)? {
+            0 => Ok(Message::Sigma(SigmaMessage::deserialize(&mut reader)?)),
+            1 => Ok(Message::SyncRequest(SyncRequest::deserialize(&mut reader)?)),
+            2 => Ok(Message::SyncResponse(SyncResponse::deserialize(&mut reader)?)),
+            3 => Ok(Message::EncryptionKey(EncryptionKey::deserialize(&mut reader)?)),
+            4 => Ok(Message::Dealing(Dealing::deserialize(&mut reader)?)),
+            5 => Ok(Message::Confirmation(Confirmation::deserialize(&mut reader)?)),
+            6 => Ok(Message::Agreement(Agreement::deserialize(&mut reader)?)),
+            _ => Err(SerializationError::InvalidData),
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{PrimeField, UniformRand};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
+use rand::{thread_rng, RngCore};
+use sha3::{
+    digest::{ExtendableOutput, Update, XofReader},
+    Shake256,
+};
+use tokio::{
+    net::TcpStream,
+    time::{timeout, Duration},
+};
+
+use optrand_pvss::{EncGroup, Scalar};
+
+use crate::network::connection::Connection;
+
+const AUTH_PERSONA: &[u8] = b"GRandLineAuth";
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Long-term authentication key of this node and the public keys of all nodes, along with the
+/// static Noise keys used when encryption is enabled.
+#[derive(Clone)]
+pub struct Identity<E: PairingEngine> {
+    pub id: usize,
+    pub secret: Scalar<E>,
+    pub public_keys: Vec<EncGroup<E>>,
+    pub encrypt: bool,
+    pub noise_secret: Vec<u8>,
+    pub noise_public_keys: Vec<Vec<u8>>,
+}
+
+/// Schnorr signature of the connecting node over the acceptor's challenge.
+#[derive(CanonicalSerialize, CanonicalDeserialize)]
+struct Hello<E: PairingEngine> {
+    id: usize,
+    r: EncGroup<E>,
+    s: Scalar<E>,
+}
+
+/// Hashes the signing context into a challenge scalar. The signature binds the nonce to both the
+/// sender and the receiver, so it cannot be relayed to another node.
+fn challenge<E: PairingEngine>(r: &EncGroup<E>, nonce: &[u8], from: usize, to: usize) -> Scalar<E> {
+    let mut bytes = Vec::new();
+    let _ = r.serialize(&mut bytes);
+    let _ = (from as u64).serialize(&mut bytes);
+    let _ = (to as u64).serialize(&mut bytes);
+
+    let mut hasher = Shake256::default();
+    hasher.update(AUTH_PERSONA);
+    hasher.update(nonce);
+    hasher.update(&bytes[..]);
+    let mut reader = hasher.finalize_xof();
+    let mut digest = [0_u8; 64];
+    XofReader::read(&mut reader, &mut digest);
+
+    Scalar::<E>::from_le_bytes_mod_order(&digest)
+}
+
+/// Sets up an outgoing connection to node `to`: runs the Noise handshake if encryption is enabled
+/// and then proves our identity.
+pub async fn handshake_outgoing<E: PairingEngine>(
+    stream: TcpStream,
+    identity: &Identity<E>,
+    to: usize,
+) -> Result<Connection, String> {
+    let mut connection = if identity.encrypt {
+        let (connection, remote) = match timeout(
+            HANDSHAKE_TIMEOUT,
+            Connection::initiate(stream, &identity.noise_secret),
+        )
+        .await
+        {
+            Ok(Ok(value)) => value,
+            Ok(Err(e)) => return Err(format!("noise handshake failed: {}", e)),
+            Err(_) => return Err("noise handshake timed out".to_string()),
+        };
+        if identity.noise_public_keys.get(to) != Some(&remote) {
+            return Err(format!("unexpected static key for node {}", to));
+        }
+        connection
+    } else {
+        Connection::plain(stream)
+    };
+
+    authenticate_outgoing(&mut connection, identity, to).await?;
+
+    Ok(connection)
+}
+
+/// Sets up an incoming connection: runs the Noise handshake if encryption is enabled and then
+/// authenticates the peer. Returns the connection and the id of the peer.
+pub async fn handshake_incoming<E: PairingEngine>(
+    stream: TcpStream,
+    identity: &Identity<E>,
+) -> Result<(Connection, usize), String> {
+    let (mut connection, remote) = if identity.encrypt {
+        match timeout(
+            HANDSHAKE_TIMEOUT,
+            Connection::respond(stream, &identity.noise_secret),
+        )
+        .await
+        {
+            Ok(Ok((connection, remote))) => (connection, Some(remote)),
+            Ok(Err(e)) => return Err(format!("noise handshake failed: {}", e)),
+            Err(_) => return Err("noise handshake timed out".to_string()),
+        }
+    } else {
+        (Connection::plain(stream), None)
+    };
+
+    let peer = authenticate_incoming(&mut connection, identity).await?;
+
+    // The encrypted channel must belong to the same node that authenticated.
+    if let Some(remote) = remote {
+        if identity.noise_public_keys.get(peer) != Some(&remote) {
+            return Err(format!("unexpected static key for node {}", peer));
+        }
+    }
+
+    Ok((connection, peer))
+}
+
+/// Proves our identity to node `to` on an outgoing connection.
+async fn authenticate_outgoing<E: PairingEngine>(
+    connection: &mut Connection,
+    identity: &Identity<E>,
+    to: usize,
+) -> Result<(), String> {
+    let nonce = match timeout(HANDSHAKE_TIMEOUT, connection.recv()).await {
+        Ok(Some(Ok(nonce))) => nonce,
+        Ok(Some(Err(e))) => return Err(format!("failed to receive challenge: {}", e)),
+        Ok(None) => return Err("connection closed during handshake".to_string()),
+        Err(_) => return Err("handshake timed out".to_string()),
+    };
+
+    let k = Scalar::<E>::rand(&mut thread_rng());
+    let r = EncGroup::<E>::prime_subgroup_generator()
+        .mul(k)
+        .into_affine();
+    let s = k + challenge::<E>(&r, &nonce, identity.id, to) * identity.secret;
+
+    let mut bytes = Vec::new();
+    Hello::<E> {
+        id: identity.id,
+        r,
+        s,
+    }
+    .serialize(&mut bytes)
+    .map_err(|e| e.to_string())?;
+
+    connection
+        .send(bytes)
+        .await
+        .map_err(|e| format!("failed to send signature: {}", e))
+}
+
+/// Challenges the node on an incoming connection and returns its authenticated id.
+async fn authenticate_incoming<E: PairingEngine>(
+    connection: &mut Connection,
+    identity: &Identity<E>,
+) -> Result<usize, String> {
+    let mut nonce = [0_u8; 32];
+    thread_rng().fill_bytes(&mut nonce);
+
+    connection
+        .send(nonce.to_vec())
+        .await
+        .map_err(|e| format!("failed to send challenge: {}", e))?;
+
+    let frame = match timeout(HANDSHAKE_TIMEOUT, connection.recv()).await {
+        Ok(Some(Ok(frame))) => frame,
+        Ok(Some(Err(e))) => return Err(format!("failed to receive signature: {}", e)),
+        Ok(None) => return Err("connection closed during handshake".to_string()),
+        Err(_) => return Err("handshake timed out".to_string()),
+    };
+
+    let hello = Hello::<E>::deserialize(&*frame).map_err(|e| e.to_string())?;
+    let public_key = match identity.public_keys.get(hello.id) {
+        Some(public_key) => *public_key,
+        None => return Err(format!("unknown node {}", hello.id)),
+    };
+
+    // g^s = R + pk^c
+    let c = challenge::<E>(&hello.r, &nonce, hello.id, identity.id);
+    let lhs = EncGroup::<E>::prime_subgroup_generator().mul(hello.s);
+    let rhs = public_key.mul(c) + hello.r.into_projective();
+
+    if lhs == rhs {
+        Ok(hello.id)
+    } else {
+        Err(format!("invalid signature from node {}", hello.id))
+    }
+}
@@ -0,0 +1,170 @@
+use std::io;
+
+use futures::{SinkExt, StreamExt};
+use snow::{Builder, HandshakeState, TransportState};
+use tokio::net::TcpStream;
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+use crate::config::NOISE_PARAMS;
+
+/// Maximum length of a single frame on the wire.
+pub const MAX_FRAME_LENGTH: usize = 4 * 1024 * 1024;
+
+const NOISE_MAX_MESSAGE: usize = 65535;
+const NOISE_TAG: usize = 16;
+const NOISE_MAX_PLAINTEXT: usize = NOISE_MAX_MESSAGE - NOISE_TAG;
+
+/// A framed TCP connection to a peer, optionally encrypted with Noise. Frames larger than a
+/// single Noise message are encrypted in chunks.
+pub struct Connection {
+    transport: Framed<TcpStream, LengthDelimitedCodec>,
+    noise: Option<TransportState>,
+}
+
+impl Connection {
+    /// Wraps a stream without encryption.
+    pub fn plain(stream: TcpStream) -> Self {
+        Self {
+            transport: Framed::new(stream, codec()),
+            noise: None,
+        }
+    }
+
+    /// Runs the initiator side of a Noise XX handshake with our static key. Returns the
+    /// connection along with the peer's static key.
+    pub async fn initiate(stream: TcpStream, secret: &[u8]) -> io::Result<(Self, Vec<u8>)> {
+        let mut transport = Framed::new(stream, codec());
+        let mut noise = builder(secret).build_initiator().map_err(noise_error)?;
+
+        // -> e
+        write_handshake(&mut transport, &mut noise).await?;
+        // <- e, ee, s, es
+        read_handshake(&mut transport, &mut noise).await?;
+        // -> s, se
+        write_handshake(&mut transport, &mut noise).await?;
+
+        Self::establish(transport, noise)
+    }
+
+    /// Runs the responder side of a Noise XX handshake with our static key. Returns the
+    /// connection along with the peer's static key.
+    pub async fn respond(stream: TcpStream, secret: &[u8]) -> io::Result<(Self, Vec<u8>)> {
+        let mut transport = Framed::new(stream, codec());
+        let mut noise = builder(secret).build_responder().map_err(noise_error)?;
+
+        // <- e
+        read_handshake(&mut transport, &mut noise).await?;
+        // -> e, ee, s, es
+        write_handshake(&mut transport, &mut noise).await?;
+        // <- s, se
+        read_handshake(&mut transport, &mut noise).await?;
+
+        Self::establish(transport, noise)
+    }
+
+    fn establish(
+        transport: Framed<TcpStream, LengthDelimitedCodec>,
+        noise: HandshakeState,
+    ) -> io::Result<(Self, Vec<u8>)> {
+        let remote = match noise.get_remote_static() {
+            Some(remote) => remote.to_vec(),
+            None => return Err(io::Error::new(io::ErrorKind::Other, "missing static key")),
+        };
+        let noise = noise.into_transport_mode().map_err(noise_error)?;
+
+        Ok((
+            Self {
+                transport,
+                noise: Some(noise),
+            },
+            remote,
+        ))
+    }
+
+    /// Sends a single frame.
+    pub async fn send(&mut self, bytes: Vec<u8>) -> io::Result<()> {
+        let bytes = match self.noise.as_mut() {
+            Some(noise) => {
+                let mut ciphertext = Vec::with_capacity(bytes.len() + NOISE_TAG);
+                let mut buffer = vec![0_u8; NOISE_MAX_MESSAGE];
+                for chunk in bytes.chunks(NOISE_MAX_PLAINTEXT) {
+                    let len = noise
+                        .write_message(chunk, &mut buffer)
+                        .map_err(noise_error)?;
+                    ciphertext.extend_from_slice(&buffer[..len]);
+                }
+                ciphertext
+            }
+            None => bytes,
+        };
+
+        self.transport.send(bytes.into()).await
+    }
+
+    /// Receives a single frame. Returns `None` once the peer closed the connection.
+    pub async fn recv(&mut self) -> Option<io::Result<Vec<u8>>> {
+        let frame = match self.transport.next().await? {
+            Ok(frame) => frame,
+            Err(e) => return Some(Err(e)),
+        };
+
+        match self.noise.as_mut() {
+            Some(noise) => {
+                let mut plaintext = Vec::with_capacity(frame.len());
+                let mut buffer = vec![0_u8; NOISE_MAX_MESSAGE];
+                for chunk in frame.chunks(NOISE_MAX_MESSAGE) {
+                    match noise.read_message(chunk, &mut buffer) {
+                        Ok(len) => plaintext.extend_from_slice(&buffer[..len]),
+                        Err(e) => return Some(Err(noise_error(e))),
+                    }
+                }
+                Some(Ok(plaintext))
+            }
+            None => Some(Ok(frame.to_vec())),
+        }
+    }
+}
+
+fn codec() -> LengthDelimitedCodec {
+    LengthDelimitedCodec::builder()
+        .max_frame_length(MAX_FRAME_LENGTH)
+        .new_codec()
+}
+
+fn builder(secret: &[u8]) -> Builder<'_> {
+    Builder::new(NOISE_PARAMS.parse().unwrap()).local_private_key(secret)
+}
+
+async fn write_handshake(
+    transport: &mut Framed<TcpStream, LengthDelimitedCodec>,
+    noise: &mut HandshakeState,
+) -> io::Result<()> {
+    let mut buffer = vec![0_u8; NOISE_MAX_MESSAGE];
+    let len = noise.write_message(&[], &mut buffer).map_err(noise_error)?;
+    transport.send(buffer[..len].to_vec().into()).await
+}
+
+async fn read_handshake(
+    transport: &mut Framed<TcpStream, LengthDelimitedCodec>,
+    noise: &mut HandshakeState,
+) -> io::Result<()> {
+    let frame = match transport.next().await {
+        Some(frame) => frame?,
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "connection closed during handshake",
+            ))
+        }
+    };
+    let mut buffer = vec![0_u8; NOISE_MAX_MESSAGE];
+    noise
+        .read_message(&frame, &mut buffer)
+        .map_err(noise_error)?;
+
+    Ok(())
+}
+
+fn noise_error(e: snow::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e)
+}
@@ -1,7 +1,11 @@
+mod auth;
+mod connection;
 mod receiver;
 mod sender;
 mod retransmitter;
 
+pub use crate::network::auth::Identity;
+pub use crate::network::connection::MAX_FRAME_LENGTH;
 pub use crate::network::receiver::*;
 pub use crate::network::sender::*;
 pub use crate::network::retransmitter::*;
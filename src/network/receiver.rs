@@ -1,33 +1,137 @@
-use std::net::SocketAddr;
+use std::{
+    collections::HashMap,
+    fmt,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
 
 use ark_ec::PairingEngine;
-use ark_serialize::CanonicalDeserialize;
-use futures::StreamExt;
-use log::{trace, warn};
+use ark_serialize::{CanonicalDeserialize, SerializationError};
+use log::{debug, info, trace, warn};
 use tokio::{
     net::{TcpListener, TcpStream},
     sync::mpsc::Sender,
+    time,
 };
-use tokio_util::codec::{Framed, LengthDelimitedCodec};
 
-use crate::message::SigmaMessage;
+use crate::{
+    message::Message,
+    network::auth::{handshake_incoming, Identity},
+};
 
 //#[cfg(test)]
 //#[path = "../tests/receiver_tests.rs"]
 //pub mod receiver_tests;
 
+/// Number of rejected frames after which a peer is disconnected and banned.
+const MAX_MISBEHAVIOUR: u64 = 10;
+
+/// Time for which a banned peer cannot reconnect.
+const BAN_DURATION: Duration = Duration::from_secs(60);
+
+/// Interval at which the frame counters are logged, if they changed.
+const METRICS_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Reasons for rejecting a received frame.
+#[derive(Debug)]
+pub enum FrameError {
+    /// The frame is not a valid message.
+    Decode(SerializationError),
+    /// The message claims to come from another node than the authenticated peer.
+    Impersonation(usize),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::Decode(e) => write!(f, "malformed message: {}", e),
+            FrameError::Impersonation(id) => write!(f, "message claims to be from {}", id),
+        }
+    }
+}
+
+/// Counters of accepted and rejected frames and of failed handshakes, over all connections.
+#[derive(Debug, Default)]
+pub struct ReceiverMetrics {
+    pub accepted: AtomicU64,
+    pub rejected: AtomicU64,
+    pub failed_handshakes: AtomicU64,
+}
+
+impl ReceiverMetrics {
+    fn snapshot(&self) -> (u64, u64, u64) {
+        (
+            self.accepted.load(Ordering::Relaxed),
+            self.rejected.load(Ordering::Relaxed),
+            self.failed_handshakes.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Rejected frames per peer and the time until which a peer is banned.
+#[derive(Default)]
+struct Misbehaviour {
+    peers: Mutex<HashMap<usize, (u64, Option<Instant>)>>,
+}
+
+impl Misbehaviour {
+    fn is_banned(&self, peer: usize) -> bool {
+        match self.peers.lock().unwrap().get(&peer) {
+            Some((_, Some(until))) => Instant::now() < *until,
+            _ => false,
+        }
+    }
+
+    /// Records a rejected frame. Returns true if the peer has to be banned.
+    fn report(&self, peer: usize) -> bool {
+        let mut peers = self.peers.lock().unwrap();
+        let (count, banned_until) = peers.entry(peer).or_insert((0, None));
+        *count += 1;
+        if *count >= MAX_MISBEHAVIOUR {
+            *count = 0;
+            *banned_until = Some(Instant::now() + BAN_DURATION);
+            return true;
+        }
+        false
+    }
+}
+
 // For each incoming request we spawn a new worker responsible to receive messages and forward them.
 pub struct SimpleReceiver<E: PairingEngine> {
     /// Address to listen to.
     address: SocketAddr,
 
     /// Channel to send received messages to.
-    deliver: Sender<SigmaMessage<E>>,
+    deliver: Sender<Message<E>>,
+
+    /// Key material used to authenticate connecting peers.
+    identity: Identity<E>,
+
+    /// Counters of accepted and rejected frames.
+    metrics: Arc<ReceiverMetrics>,
+
+    /// Misbehaviour of the connected peers.
+    misbehaviour: Arc<Misbehaviour>,
 }
 
 impl<E: PairingEngine> SimpleReceiver<E> {
-    pub fn new(address: SocketAddr, deliver: Sender<SigmaMessage<E>>) -> Self {
-        Self { address, deliver }
+    pub fn new(address: SocketAddr, deliver: Sender<Message<E>>, identity: Identity<E>) -> Self {
+        Self {
+            address,
+            deliver,
+            identity,
+            metrics: Arc::new(ReceiverMetrics::default()),
+            misbehaviour: Arc::new(Misbehaviour::default()),
+        }
+    }
+
+    /// Returns the frame counters of this receiver.
+    pub fn metrics(&self) -> Arc<ReceiverMetrics> {
+        self.metrics.clone()
     }
 
     pub async fn run(&self) {
@@ -36,6 +140,25 @@ impl<E: PairingEngine> SimpleReceiver<E> {
             .await
             .expect("Failed to bind TCP port");
 
+        // Report the counters now and then, instead of logging every failed handshake.
+        let metrics = self.metrics.clone();
+        tokio::spawn(async move {
+            let mut interval = time::interval(METRICS_INTERVAL);
+            let mut last = (0, 0, 0);
+            loop {
+                interval.tick().await;
+                let current = metrics.snapshot();
+                if current != last {
+                    let (accepted, rejected, failed_handshakes) = current;
+                    info!(
+                        "Received {} frames, rejected {}, {} failed handshakes",
+                        accepted, rejected, failed_handshakes
+                    );
+                    last = current;
+                }
+            }
+        });
+
         // Wait for incoming connections. If someone wants to connect spawn a new worker who is
         // responsible for handling the connection.
         loop {
@@ -48,31 +171,92 @@ impl<E: PairingEngine> SimpleReceiver<E> {
             };
             trace!("Incoming connection from {:?}", addr);
             // Spawn worker with socket as argument and channel, where he can put his data into.
-            Self::spawn_worker(socket, self.deliver.clone()).await;
+            Self::spawn_worker(
+                socket,
+                addr,
+                self.deliver.clone(),
+                self.identity.clone(),
+                self.metrics.clone(),
+                self.misbehaviour.clone(),
+            )
+            .await;
         }
     }
 
-    async fn spawn_worker(socket: TcpStream, deliver: Sender<SigmaMessage<E>>) {
+    async fn spawn_worker(
+        socket: TcpStream,
+        addr: SocketAddr,
+        deliver: Sender<Message<E>>,
+        identity: Identity<E>,
+        metrics: Arc<ReceiverMetrics>,
+        misbehaviour: Arc<Misbehaviour>,
+    ) {
         trace!("Spawning worker");
         tokio::spawn(async move {
-            let transport = Framed::new(socket, LengthDelimitedCodec::new());
-            let (_, mut reader) = transport.split();
-            while let Some(frame) = reader.next().await {
-                match frame {
-                    Ok(message) => {
-                        // Deserialize network message.
-                        let mes = SigmaMessage::deserialize(&*message).unwrap();
-                        trace!("Received message from {}", mes.id);
+            // Find out which node is on the other end before accepting any message.
+            let (mut connection, peer) = match handshake_incoming(socket, &identity).await {
+                Ok(value) => value,
+                Err(e) => {
+                    // Anyone can connect, so this is only worth a debug message. The failures
+                    // are counted in the metrics.
+                    metrics.failed_handshakes.fetch_add(1, Ordering::Relaxed);
+                    debug!("Failed to authenticate peer {}: {}", addr, e);
+                    return;
+                }
+            };
+            trace!("Authenticated node {}", peer);
+
+            if misbehaviour.is_banned(peer) {
+                warn!("Refusing connection from banned node {}", peer);
+                return;
+            }
+
+            while let Some(frame) = connection.recv().await {
+                let bytes = match frame {
+                    Ok(bytes) => bytes,
+                    Err(e) => {
+                        // This includes frames exceeding the maximum frame length.
+                        metrics.rejected.fetch_add(1, Ordering::Relaxed);
+                        warn!("Error receiving data from node {}: {}", peer, e);
+                        return;
+                    }
+                };
+
+                match Self::decode(&bytes, peer) {
+                    Ok(mes) => {
+                        trace!("Received message from {}", mes.id());
+                        metrics.accepted.fetch_add(1, Ordering::Relaxed);
                         // Put message into channel, such that it can be retreived with the receiving
                         // end of the channel.
-                        deliver.send(mes).await.unwrap();
+                        if deliver.send(mes).await.is_err() {
+                            return;
+                        }
                     }
                     Err(e) => {
-                        warn!("Error receiving data {}", e);
-                        return;
+                        let rejected = metrics.rejected.fetch_add(1, Ordering::Relaxed) + 1;
+                        warn!(
+                            "Rejected frame from node {}: {} ({} rejected frames in total)",
+                            peer, e, rejected
+                        );
+                        if misbehaviour.report(peer) {
+                            warn!("Disconnecting and banning node {}", peer);
+                            return;
+                        }
                     }
                 }
             }
         });
     }
+
+    /// Deserializes a frame received from an authenticated peer.
+    fn decode(bytes: &[u8], peer: usize) -> Result<Message<E>, FrameError> {
+        let mes = Message::deserialize(bytes).map_err(FrameError::Decode)?;
+
+        // Drop messages claiming to come from another node.
+        if mes.id() != peer {
+            return Err(FrameError::Impersonation(mes.id()));
+        }
+
+        Ok(mes)
+    }
 }
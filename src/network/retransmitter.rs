@@ -1,45 +1,221 @@
-use std::net::SocketAddr;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    net::SocketAddr,
+};
 
 use ark_ec::PairingEngine;
 use futures::{stream::FuturesUnordered, StreamExt};
-use log::trace;
-use tokio::sync::mpsc::{Receiver, Sender};
+use log::{debug, trace};
+use rand::{thread_rng, Rng};
+use tokio::sync::{
+    mpsc::{Receiver, Sender},
+    watch,
+};
 use tokio::time::{sleep, Duration};
 
-use crate::message::SigmaMessage;
+use crate::message::Message;
 
+/// Outcome of a delivery attempt, reported by the sender.
+#[derive(Debug)]
+pub enum Delivery<E: PairingEngine> {
+    /// The message could not be sent to the given peer.
+    Failed(Message<E>, SocketAddr),
+    /// A connection to the given peer was established.
+    Connected(SocketAddr),
+}
+
+/// Controls how often failed messages are retried.
+#[derive(Clone, Copy, Debug)]
+pub struct RetransmitPolicy {
+    /// Delay before the first retry. Doubles with every failed attempt.
+    pub initial_delay: Duration,
+    /// Upper bound for the delay between retries.
+    pub max_delay: Duration,
+    /// Number of consecutive failed attempts after which the peer is considered down.
+    pub breaker_threshold: u32,
+    /// Delay between probes while a peer is considered down.
+    pub breaker_cooldown: Duration,
+}
+
+impl Default for RetransmitPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            breaker_threshold: 5,
+            breaker_cooldown: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetransmitPolicy {
+    /// Delay before the next attempt after `failures` consecutive failed attempts, with equal
+    /// jitter so that the nodes do not retry in lockstep.
+    fn delay(&self, failures: u32) -> Duration {
+        let delay = if failures >= self.breaker_threshold {
+            self.breaker_cooldown
+        } else {
+            self.initial_delay
+                .checked_mul(1 << failures.min(16))
+                .unwrap_or(self.max_delay)
+                .min(self.max_delay)
+        };
+
+        let half = delay / 2;
+        let jitter = thread_rng().gen_range(0, half.as_millis() as u64 + 1);
+        half + Duration::from_millis(jitter)
+    }
+}
+
+/// Messages waiting to be retried for a single peer.
+struct PeerQueue<E: PairingEngine> {
+    messages: VecDeque<Message<E>>,
+    failures: u32,   // Consecutive failed attempts
+    scheduled: bool, // Whether a retry is pending
+}
+
+impl<E: PairingEngine> Default for PeerQueue<E> {
+    fn default() -> Self {
+        Self {
+            messages: VecDeque::new(),
+            failures: 0,
+            scheduled: false,
+        }
+    }
+}
+
+/// Collects messages that could not be delivered and hands them back to the sender, addressed to
+/// the peer they failed for only. Retries back off exponentially per peer. Once a peer failed
+/// `breaker_threshold` times in a row, only a single probe is sent per cooldown until a
+/// connection succeeds again. The peers in that state are published to the sender, which parks
+/// new messages for them here instead of connecting. Sigmas of epochs the core has already
+/// finished and sync messages are dropped.
 pub struct SimpleRetransmitter<E: PairingEngine> {
-    rx: Receiver<(SigmaMessage<E>, SocketAddr)>,
-    tx: Sender<SigmaMessage<E>>,
+    rx: Receiver<Delivery<E>>,
+    tx: Sender<(Message<E>, SocketAddr)>,
+    epoch: watch::Receiver<u64>,
+    down: watch::Sender<HashSet<SocketAddr>>, // Peers whose breaker is open
+    policy: RetransmitPolicy,
+    queues: HashMap<SocketAddr, PeerQueue<E>>,
 }
 
 impl<E: PairingEngine> SimpleRetransmitter<E> {
     pub fn new(
-        rx: Receiver<(SigmaMessage<E>, SocketAddr)>,
-        tx: Sender<SigmaMessage<E>>,
+        rx: Receiver<Delivery<E>>,
+        tx: Sender<(Message<E>, SocketAddr)>,
+        epoch: watch::Receiver<u64>,
+        down: watch::Sender<HashSet<SocketAddr>>,
+        policy: RetransmitPolicy,
     ) -> Self {
-        Self { rx, tx }
+        Self {
+            rx,
+            tx,
+            epoch,
+            down,
+            policy,
+            queues: HashMap::new(),
+        }
     }
 
     pub async fn run(&mut self) {
         let mut pending = FuturesUnordered::new();
         loop {
             tokio::select! {
-                Some((mes, _)) = self.rx.recv() => {
-                    pending.push(Self::delay(mes));
-                }
-                Some(mes) = pending.next() => self.tx.send(mes).await.unwrap(),
+                Some(delivery) = self.rx.recv() => match delivery {
+                    Delivery::Failed(mes, address) => {
+                        if is_stale(&mes, *self.epoch.borrow()) {
+                            trace!(
+                                "Dropping stale message (id: {}, epoch: [{}]) for {:?}",
+                                mes.id(),
+                                mes.epoch(),
+                                address
+                            );
+                            continue;
+                        }
+                        trace!(
+                            "Delaying message (id: {}, epoch: [{}]) for {:?}",
+                            mes.id(),
+                            mes.epoch(),
+                            address
+                        );
+
+                        let queue = self.queues.entry(address).or_default();
+                        queue.messages.push_back(mes);
+                        if !queue.scheduled {
+                            queue.scheduled = true;
+                            pending.push(Self::delay(address, self.policy.delay(queue.failures)));
+                        }
+                    }
+                    Delivery::Connected(address) => {
+                        if let Some(queue) = self.queues.get_mut(&address) {
+                            if queue.failures >= self.policy.breaker_threshold {
+                                debug!("Peer {:?} is reachable again", address);
+                                self.down.send_modify(|down| {
+                                    down.remove(&address);
+                                });
+                            }
+                            queue.failures = 0;
+                            // Flush the remaining messages right away.
+                            if !queue.messages.is_empty() && !queue.scheduled {
+                                queue.scheduled = true;
+                                pending.push(Self::delay(address, Duration::from_millis(0)));
+                            }
+                        }
+                    }
+                },
+                Some(address) = pending.next() => self.retry(address).await,
             }
         }
     }
 
-    async fn delay(message: SigmaMessage<E>) -> SigmaMessage<E> {
-        trace!(
-            "Delaying message (id: {}, epoch: [{}]",
-            message.id,
-            message.epoch
-        );
-        sleep(Duration::from_millis(100)).await;
-        message
+    /// Hands the queued messages for a peer back to the sender. While the breaker is open, only
+    /// the most recent message is sent as a probe.
+    async fn retry(&mut self, address: SocketAddr) {
+        let epoch = *self.epoch.borrow();
+        let queue = match self.queues.get_mut(&address) {
+            Some(queue) => queue,
+            None => return,
+        };
+        queue.scheduled = false;
+        queue.messages.retain(|mes| !is_stale(mes, epoch));
+
+        let messages = if queue.failures >= self.policy.breaker_threshold {
+            queue.messages.pop_back().into_iter().collect::<Vec<_>>()
+        } else {
+            queue.messages.drain(..).collect::<Vec<_>>()
+        };
+        if messages.is_empty() {
+            return;
+        }
+
+        queue.failures += 1;
+        if queue.failures == self.policy.breaker_threshold {
+            debug!(
+                "Peer {:?} failed {} times in a row, probing every {:?}",
+                address, queue.failures, self.policy.breaker_cooldown
+            );
+            self.down.send_modify(|down| {
+                down.insert(address);
+            });
+        }
+
+        for mes in messages {
+            self.tx.send((mes, address)).await.unwrap();
+        }
+    }
+
+    async fn delay(address: SocketAddr, delay: Duration) -> SocketAddr {
+        sleep(delay).await;
+        address
+    }
+}
+
+/// Sigmas of finished epochs are useless to the peer. Sync messages are never retried: the core
+/// asks another peer after a timeout anyway, and a late response only repeats its work.
+fn is_stale<E: PairingEngine>(message: &Message<E>, epoch: u64) -> bool {
+    match message {
+        Message::Sigma(sigma) => sigma.epoch < epoch,
+        Message::SyncRequest(_) | Message::SyncResponse(_) => true,
+        _ => false,
     }
 }
@@ -1,41 +1,68 @@
-use std::{collections::HashMap, net::SocketAddr};
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+};
 
 use ark_ec::PairingEngine;
 use ark_serialize::CanonicalSerialize;
-use futures::SinkExt;
 use log::{trace, warn};
 use tokio::{
     net::TcpStream,
     sync::{
-        mpsc::{channel, Receiver, Sender},
-        oneshot,
+        mpsc::{channel, error::TrySendError, Receiver, Sender},
+        watch,
     },
+    time::{timeout, Duration},
 };
-use tokio_util::codec::{Framed, LengthDelimitedCodec};
 
-use crate::message::SigmaMessage;
+use crate::{
+    message::Message,
+    network::{
+        auth::{handshake_outgoing, Identity},
+        connection::Connection,
+        retransmitter::Delivery,
+    },
+};
+
+/// Maximum time to establish a TCP connection to a peer.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
 
 pub struct SimpleSender<E: PairingEngine> {
     // Channel for communication between NetworkSender and other threads.
-    transmit: Receiver<SigmaMessage<E>>,
+    transmit: Receiver<Message<E>>,
 
     // Channel for communication between NetworkSender and NetworkRetransmitter
-    retransmit: Sender<(SigmaMessage<E>, SocketAddr)>,
+    retransmit: Sender<Delivery<E>>,
+
+    // Messages handed back by the NetworkRetransmitter, addressed to a single peer.
+    retransmitted: Receiver<(Message<E>, SocketAddr)>,
 
-    // Socket addresses of all nodes (other than the own node).
-    addresses: Vec<SocketAddr>,
+    // Peers whose breaker is open, published by the NetworkRetransmitter.
+    down: watch::Receiver<HashSet<SocketAddr>>,
+
+    // Ids and socket addresses of all nodes (other than the own node).
+    peers: Vec<(usize, SocketAddr)>,
+
+    // Key used to authenticate to the peers.
+    identity: Identity<E>,
 }
 
 impl<E: PairingEngine> SimpleSender<E> {
     pub fn new(
-        transmit: Receiver<SigmaMessage<E>>,
-        retransmit: Sender<(SigmaMessage<E>, SocketAddr)>,
-        addresses: Vec<SocketAddr>,
+        transmit: Receiver<Message<E>>,
+        retransmit: Sender<Delivery<E>>,
+        retransmitted: Receiver<(Message<E>, SocketAddr)>,
+        down: watch::Receiver<HashSet<SocketAddr>>,
+        peers: Vec<(usize, SocketAddr)>,
+        identity: Identity<E>,
     ) -> Self {
         Self {
             transmit,
             retransmit,
-            addresses,
+            retransmitted,
+            down,
+            peers,
+            identity,
         }
     }
 
@@ -43,96 +70,176 @@ impl<E: PairingEngine> SimpleSender<E> {
     // dedicated channels for every worker.
     pub async fn run(&mut self) {
         // Keep track of workers. Maps socket address to sender channel for corresponding worker.
-        let mut senders = HashMap::<SocketAddr, Sender<SigmaMessage<E>>>::new();
-
-        while let Some(mes) = self.transmit.recv().await {
-            // Always broadcast
-            for address in &self.addresses {
-                // Spawn is true if there is no sender channel or sending over the channel failed.
-                let spawn = match senders.get(&address) {
-                    Some(tx) => tx.send(mes.clone()).await.is_err(),
-                    None => true,
-                };
-
-                if spawn {
-                    // Spawn a new worker.
-                    let (tx_ok, rx_ok) = oneshot::channel();
-                    let tx = Self::spawn_worker(*address, self.retransmit.clone(), tx_ok).await;
-
-                    let mut retransmit = false;
-
-                    match rx_ok.await {
-                        Ok(res) => {
-                            if res {
-                                // Send the new worker the message
-                                if let Ok(_) = tx.send(mes.clone()).await {
-                                    senders.insert(*address, tx);
-                                }
-                            } else {
-                                warn!("Worker failed to connect to {:?}", address);
-                                retransmit = true;
+        let mut senders = HashMap::<SocketAddr, Sender<Message<E>>>::new();
+
+        loop {
+            tokio::select! {
+                Some(mes) = self.transmit.recv() => {
+                    // Addressed messages only go to their recipient, all others are broadcast.
+                    match mes.recipient() {
+                        Some(to) => match self.peers.iter().find(|(peer, _)| *peer == to) {
+                            Some((peer, address)) => {
+                                self.send(&mut senders, *peer, *address, mes, false).await
+                            }
+                            None => warn!("Dropping message to unknown peer {}", to),
+                        },
+                        None => {
+                            for (peer, address) in &self.peers {
+                                self.send(&mut senders, *peer, *address, mes.clone(), false)
+                                    .await;
                             }
-                        }
-                        Err(e) => {
-                            warn!("Failed to spawn worker for {:?}: {}", address, e);
-                            retransmit = true;
                         }
                     }
-
-                    if retransmit {
-                        self.retransmit.send((mes.clone(), address.clone())).await.unwrap();
+                }
+                Some((mes, address)) = self.retransmitted.recv() => {
+                    // Retransmissions only go to the peer they failed for. They are sent even if
+                    // its breaker is open, as they are the probes that close it again.
+                    match self.peers.iter().find(|(_, a)| *a == address) {
+                        Some((peer, _)) => {
+                            self.send(&mut senders, *peer, address, mes, true).await
+                        }
+                        None => warn!("Dropping retransmission to unknown peer {:?}", address),
                     }
                 }
+                else => break,
             }
         }
     }
 
-    async fn spawn_worker(
+    // Sends a message to a single peer, spawning a worker for it if needed. Workers queue the
+    // messages while they connect, so an unreachable peer never holds up the other peers. New
+    // messages for a peer whose breaker is open are parked at the retransmitter instead of
+    // connecting again.
+    async fn send(
+        &self,
+        senders: &mut HashMap<SocketAddr, Sender<Message<E>>>,
+        peer: usize,
         address: SocketAddr,
-        retransmit: Sender<(SigmaMessage<E>, SocketAddr)>,
-        ok: oneshot::Sender<bool>,
-    ) -> Sender<SigmaMessage<E>> {
+        mes: Message<E>,
+        probe: bool,
+    ) {
+        let mes = match senders.get(&address) {
+            Some(tx) => match tx.try_send(mes) {
+                Ok(()) => return,
+                Err(TrySendError::Full(mes)) => {
+                    warn!("Queue for {:?} is full", address);
+                    self.retransmit
+                        .send(Delivery::Failed(mes, address))
+                        .await
+                        .unwrap();
+                    return;
+                }
+                // The worker is gone, spawn a new one.
+                Err(TrySendError::Closed(mes)) => mes,
+            },
+            None => mes,
+        };
+
+        if !probe && self.down.borrow().contains(&address) {
+            trace!("Parking message for unreachable peer {:?}", address);
+            self.retransmit
+                .send(Delivery::Failed(mes, address))
+                .await
+                .unwrap();
+            return;
+        }
+
+        let tx = Self::spawn_worker(
+            peer,
+            address,
+            self.identity.clone(),
+            self.retransmit.clone(),
+        );
+        // The channel is new, so it has room for the message.
+        let _ = tx.try_send(mes);
+        senders.insert(address, tx);
+    }
+
+    fn spawn_worker(
+        peer: usize,
+        address: SocketAddr,
+        identity: Identity<E>,
+        retransmit: Sender<Delivery<E>>,
+    ) -> Sender<Message<E>> {
         // Create channel for communication with SimpleSender.
-        let (tx, mut rx): (Sender<SigmaMessage<E>>, Receiver<SigmaMessage<E>>) = channel(1_000);
+        let (tx, mut rx): (Sender<Message<E>>, Receiver<Message<E>>) = channel(1_000);
 
         tokio::spawn(async move {
-            // Connect to address.
-            let stream = match TcpStream::connect(address).await {
-                Ok(stream) => {
-                    trace!("Outgoing connection established with {:?}", address);
-                    let _ = ok.send(true);
-                    stream
-                }
-                Err(e) => {
-                    warn!("Failed to connect to {:?}: {}", address, e);
-                    let _ = ok.send(false);
-                    return;
-                }
+            let mut connection = match Self::connect(peer, address, &identity).await {
+                Some(connection) => connection,
+                None => return Self::fail(&mut rx, None, address, &retransmit).await,
             };
-
-            // Frame the TCP stream
-            let mut transport = Framed::new(stream, LengthDelimitedCodec::new());
+            let _ = retransmit.send(Delivery::Connected(address)).await;
 
             while let Some(mes) = rx.recv().await {
                 // Serialize the message
                 let mut bytes = Vec::new();
                 mes.serialize(&mut bytes).unwrap();
 
-                match transport.send(bytes.into()).await {
+                match connection.send(bytes).await {
                     Ok(_) => trace!("Successfully sent message to {:?}", address),
                     Err(e) => {
                         warn!("Failed to send message to {:?}: {}", address, e);
-                        retransmit
-                            .send((mes.clone(), address.clone()))
-                            .await
-                            .unwrap();
-                        return;
+                        return Self::fail(&mut rx, Some(mes), address, &retransmit).await;
                     }
                 }
             }
         });
         tx
     }
+
+    // Connects to a peer, then frames (and possibly encrypts) the TCP stream and authenticates
+    // to the peer before any message is sent.
+    async fn connect(
+        peer: usize,
+        address: SocketAddr,
+        identity: &Identity<E>,
+    ) -> Option<Connection> {
+        let stream = match timeout(CONNECT_TIMEOUT, TcpStream::connect(address)).await {
+            Ok(Ok(stream)) => {
+                trace!("Outgoing connection established with {:?}", address);
+                stream
+            }
+            Ok(Err(e)) => {
+                warn!("Failed to connect to {:?}: {}", address, e);
+                return None;
+            }
+            Err(_) => {
+                warn!("Timed out connecting to {:?}", address);
+                return None;
+            }
+        };
+
+        match handshake_outgoing(stream, identity, peer).await {
+            Ok(connection) => Some(connection),
+            Err(e) => {
+                warn!("Failed to authenticate to {:?}: {}", address, e);
+                None
+            }
+        }
+    }
+
+    // Hands the failed message and everything still queued for the peer to the retransmitter.
+    async fn fail(
+        rx: &mut Receiver<Message<E>>,
+        failed: Option<Message<E>>,
+        address: SocketAddr,
+        retransmit: &Sender<Delivery<E>>,
+    ) {
+        rx.close();
+        if let Some(mes) = failed {
+            retransmit
+                .send(Delivery::Failed(mes, address))
+                .await
+                .unwrap();
+        }
+        while let Some(mes) = rx.recv().await {
+            retransmit
+                .send(Delivery::Failed(mes, address))
+                .await
+                .unwrap();
+        }
+    }
 }
 
 // pub struct SimpleSender {
@@ -1,40 +1,92 @@
 use ark_ec::PairingEngine;
 use log::debug;
 
-use std::net::SocketAddr;
+use std::{
+    collections::HashSet,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
 use tokio::{
-    sync::mpsc::channel,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        watch,
+    },
     time::{sleep, Duration},
 };
 
 use crate::{
-    config::Input,
+    beacon::BeaconOutput,
+    config::{Input, Parameters},
     core::Core,
-    network::{SimpleReceiver, SimpleRetransmitter, SimpleSender},
+    message::Message,
+    network::{Identity, RetransmitPolicy, SimpleReceiver, SimpleRetransmitter, SimpleSender},
+    store::BeaconStore,
 };
 
+/// How a node runs, besides its setup and the addresses of the committee.
+pub struct NodeOptions<E: PairingEngine> {
+    pub store: Option<BeaconStore<E>>, // Beacon history to persist to and resume from
+    pub parameters: Parameters,        // Committee-wide protocol parameters
+    pub policy: RetransmitPolicy,
+    pub verify_all: bool, // Also verify sigmas beyond the t + 1 needed for reconstruction
+}
+
 pub async fn new<E: PairingEngine>(
     id: usize,
     nodes: Vec<SocketAddr>,
-    num_participants: usize,
-    num_faults: usize,
+    listen_address: SocketAddr,
+    tx_output: Sender<BeaconOutput<E>>,
     input: Input<E>,
+    options: NodeOptions<E>,
 ) {
+    let identity = Identity {
+        id,
+        secret: input.auth_sk,
+        public_keys: input.auth_pks.clone(),
+        encrypt: options.parameters.encrypt,
+        noise_secret: input.noise_sk.clone(),
+        noise_public_keys: input.noise_pks.clone(),
+    };
+    let (tx_send, rx_rec, tx_epoch) =
+        spawn_network(&nodes, listen_address, identity, options.policy).await;
+
+    Core::spawn(id, tx_send, rx_rec, tx_output, tx_epoch, input, options).await;
+}
+
+/// Starts the networking of a node: returns the channel to send messages to the other nodes, the
+/// channel delivering theirs and the current epoch, which the retransmitter uses to drop stale
+/// sigmas.
+pub async fn spawn_network<E: PairingEngine>(
+    nodes: &[SocketAddr],
+    listen_address: SocketAddr,
+    identity: Identity<E>,
+    policy: RetransmitPolicy,
+) -> (Sender<Message<E>>, Receiver<Message<E>>, watch::Sender<u64>) {
     // Create a channel for networking.
     let (tx_rec, rx_rec) = channel(1_000);
     let (tx_send, rx_send) = channel(1_000);
     let (tx_retransmit, rx_retransmit) = channel(1_000);
+    let (tx_retransmitted, rx_retransmitted) = channel(1_000);
+    let (tx_epoch, rx_epoch) = watch::channel(0);
+    let (tx_down, rx_down) = watch::channel(HashSet::new());
 
-    let mut addresses = nodes.clone();
-    addresses.remove(id);
-    let listen_address = format!("0.0.0.0:{}", 9000 + id)
-        .parse::<SocketAddr>()
-        .unwrap();
-
+    let peers = nodes
+        .iter()
+        .cloned()
+        .enumerate()
+        .filter(|(i, _)| *i != identity.id)
+        .collect::<Vec<_>>();
     // Create a retransmitter, receiver and sender.
-    let mut retransmitter = SimpleRetransmitter::<E>::new(rx_retransmit, tx_send.clone());
-    let receiver = SimpleReceiver::new(listen_address, tx_rec);
-    let mut sender = SimpleSender::new(rx_send, tx_retransmit, addresses.clone());
+    let mut retransmitter =
+        SimpleRetransmitter::<E>::new(rx_retransmit, tx_retransmitted, rx_epoch, tx_down, policy);
+    let receiver = SimpleReceiver::new(listen_address, tx_rec, identity.clone());
+    let mut sender = SimpleSender::new(
+        rx_send,
+        tx_retransmit,
+        rx_retransmitted,
+        rx_down,
+        peers,
+        identity,
+    );
 
     // Run retransmitter, receiver and sender.
     debug!("Setting up network.. Listen address: {}", listen_address);
@@ -52,5 +104,15 @@ pub async fn new<E: PairingEngine>(
 
     sleep(Duration::from_millis(100)).await;
 
-    Core::spawn(id, tx_send, rx_rec, num_participants, num_faults, input).await;
+    (tx_send, rx_rec, tx_epoch)
+}
+
+/// Address to bind to if none is given: all interfaces of the same IP version, on the port that
+/// the node advertises to the committee.
+pub fn default_listen_address(advertised: SocketAddr) -> SocketAddr {
+    let ip = match advertised.ip() {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    SocketAddr::new(ip, advertised.port())
 }
@@ -0,0 +1,171 @@
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+    marker::PhantomData,
+    ops::RangeBounds,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use ark_ec::PairingEngine;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+use log::warn;
+use tokio::task;
+
+use crate::beacon::BeaconOutput;
+
+#[cfg(test)]
+#[path = "tests/store_tests.rs"]
+mod store_tests;
+
+/// Append-only, on-disk beacon chain. Every record is a little-endian u32 length followed by the
+/// serialized epoch, beacon value, reconstructed sigma and the contributing sigma messages.
+/// An in-memory index maps each epoch to the offset of its record.
+pub struct BeaconStore<E: PairingEngine> {
+    file: Arc<File>,           // Append handle, shared with the background syncs
+    reader: Arc<Mutex<File>>,  // Read handle, shared with the BeaconReaders
+    index: BTreeMap<u64, u64>, // Maps epoch -> record offset
+    end: u64,
+    _engine: PhantomData<E>,
+}
+
+impl<E: PairingEngine> BeaconStore<E> {
+    /// Opens (or creates) the store at the given path and rebuilds the epoch index. A trailing,
+    /// partially written record (e.g. after a crash) is cut off.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+
+        // Only the length and the leading epoch of each record are read, the rest is skipped.
+        let size = file.metadata()?.len();
+        let mut index = BTreeMap::new();
+        let mut offset = 0;
+        let mut scanner = BufReader::new(&file);
+        scanner.seek(SeekFrom::Start(0))?;
+        while offset + 4 <= size {
+            let mut len = [0_u8; 4];
+            scanner.read_exact(&mut len)?;
+            let len = u32::from_le_bytes(len) as u64;
+            if offset + 4 + len > size {
+                break;
+            }
+            if len < 8 {
+                return Err(invalid_data(SerializationError::InvalidData));
+            }
+            let epoch = u64::deserialize(&mut scanner).map_err(invalid_data)?;
+            scanner.seek_relative(len as i64 - 8)?;
+            index.insert(epoch, offset);
+            offset += 4 + len;
+        }
+
+        if offset < size {
+            file.set_len(offset)?;
+        }
+
+        Ok(Self {
+            reader: Arc::new(Mutex::new(File::open(path)?)),
+            file: Arc::new(file),
+            index,
+            end: offset,
+            _engine: PhantomData,
+        })
+    }
+
+    /// Appends a finished beacon to the chain. It is flushed to disk on the blocking thread pool,
+    /// so this must be called within a tokio runtime.
+    pub fn append(&mut self, output: &BeaconOutput<E>) -> io::Result<()> {
+        let mut record = Vec::new();
+        output.serialize(&mut record).map_err(invalid_data)?;
+
+        let mut bytes = Vec::with_capacity(4 + record.len());
+        bytes.extend_from_slice(&(record.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&record);
+
+        (&*self.file).write_all(&bytes)?;
+        let file = self.file.clone();
+        task::spawn_blocking(move || {
+            if let Err(e) = file.sync_data() {
+                warn!("Failed to flush the beacon history: {}", e);
+            }
+        });
+
+        self.index.insert(output.epoch, self.end);
+        self.end += bytes.len() as u64;
+
+        Ok(())
+    }
+
+    /// Returns the beacon of a given epoch, if it is stored.
+    pub fn get(&self, epoch: u64) -> io::Result<Option<BeaconOutput<E>>> {
+        match self.index.get(&epoch) {
+            Some(offset) => read_at(&self.reader, *offset).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the beacon with the highest stored epoch.
+    pub fn latest(&self) -> io::Result<Option<BeaconOutput<E>>> {
+        match self.index.iter().next_back() {
+            Some((_, offset)) => read_at(&self.reader, *offset).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns all stored beacons whose epoch lies in the given range, ordered by epoch.
+    pub fn range<R: RangeBounds<u64>>(&self, epochs: R) -> io::Result<Vec<BeaconOutput<E>>> {
+        self.reader(epochs).collect()
+    }
+
+    /// Returns a reader for the stored beacons whose epoch lies in the given range, which reads
+    /// them one at a time, in epoch order. It can be moved to another thread, e.g. to read the
+    /// beacons on the blocking thread pool.
+    pub fn reader<R: RangeBounds<u64>>(&self, epochs: R) -> BeaconReader<E> {
+        BeaconReader {
+            file: self.reader.clone(),
+            offsets: self
+                .index
+                .range(epochs)
+                .map(|(_, offset)| *offset)
+                .collect::<Vec<_>>()
+                .into_iter(),
+            _engine: PhantomData,
+        }
+    }
+}
+
+/// Iterator over a range of stored beacons, see `BeaconStore::reader`.
+pub struct BeaconReader<E: PairingEngine> {
+    file: Arc<Mutex<File>>,
+    offsets: std::vec::IntoIter<u64>,
+    _engine: PhantomData<E>,
+}
+
+impl<E: PairingEngine> Iterator for BeaconReader<E> {
+    type Item = io::Result<BeaconOutput<E>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.offsets.next()?;
+        Some(read_at(&self.file, offset))
+    }
+}
+
+fn read_at<E: PairingEngine>(file: &Mutex<File>, offset: u64) -> io::Result<BeaconOutput<E>> {
+    let mut file = file.lock().unwrap();
+    file.seek(SeekFrom::Start(offset))?;
+
+    let mut len = [0_u8; 4];
+    file.read_exact(&mut len)?;
+    let mut record = vec![0_u8; u32::from_le_bytes(len) as usize];
+    file.read_exact(&mut record)?;
+
+    BeaconOutput::<E>::deserialize(&*record).map_err(invalid_data)
+}
+
+fn invalid_data(e: SerializationError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
@@ -0,0 +1,74 @@
+//! Fixtures shared by the unit tests and the benchmarks (`--features test-support`). Not part of
+//! the public API.
+
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::UniformRand;
+use ark_poly::{polynomial::UVPolynomial, Polynomial};
+use rand::thread_rng;
+use std::ops::Neg;
+
+use optrand_pvss::{
+    modified_scrape::{config::Config, poly::Polynomial as Poly, srs::SRS},
+    EncGroup, Scalar,
+};
+
+use crate::{
+    beacon::{compute_sigma, Generator, Precomputed},
+    config::Commitment,
+    message::SigmaMessage,
+};
+
+/// A committee along with the honest sigma messages of all its members for one epoch.
+pub struct Epoch<E: PairingEngine> {
+    pub config: Config<E>,
+    pub sks: Vec<EncGroup<E>>,
+    pub a_is: Vec<Scalar<E>>,
+    pub commitments: Vec<Commitment<E>>,
+    pub precomputed: Precomputed<E>,
+    pub generator: Generator<E>,
+    pub messages: Vec<SigmaMessage<E>>,
+}
+
+/// Sets up a committee of `num_participants` members with threshold `num_faults`, whose secret
+/// keys are shares g1^f(id + 1) of a random group key, and computes their sigmas for `epoch`.
+pub fn epoch<E: PairingEngine>(num_participants: usize, num_faults: usize, epoch: u64) -> Epoch<E> {
+    let rng = &mut thread_rng();
+    let config = Config {
+        srs: SRS::<E>::setup(rng).unwrap(),
+        degree: num_faults,
+        num_participants,
+    };
+    let generator = Generator::new(epoch);
+
+    let f = Poly::<E>::rand(num_faults, rng);
+    let sks = (0..num_participants)
+        .map(|id| {
+            let share = f.evaluate(&Scalar::<E>::from(id as u64 + 1));
+            config.srs.g1.mul(share).into_affine()
+        })
+        .collect::<Vec<_>>();
+    let a_is = (0..num_participants)
+        .map(|_| Scalar::<E>::rand(rng))
+        .collect::<Vec<_>>();
+
+    let commitments = (0..num_participants)
+        .map(|id| Commitment {
+            id,
+            part1: config.srs.g2.mul(a_is[id]).into_affine(),
+            part2: sks[id] + config.srs.g1.mul(a_is[id]).neg().into_affine(),
+        })
+        .collect::<Vec<_>>();
+    let messages = (0..num_participants)
+        .map(|id| compute_sigma(&config, id, &sks[id].into(), a_is[id], epoch, &generator))
+        .collect::<Vec<_>>();
+
+    Epoch {
+        precomputed: Precomputed::new(&config, &commitments),
+        config,
+        sks,
+        a_is,
+        commitments,
+        generator,
+        messages,
+    }
+}
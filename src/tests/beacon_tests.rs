@@ -0,0 +1,163 @@
+use ark_bls12_381::Bls12_381;
+use std::{collections::HashSet, ops::Neg};
+
+use crate::{
+    beacon::{beacon_value, reconstruct_sigma, verify_batch, verify_beacon, BeaconOutput},
+    message::SigmaMessage,
+    test_support::{self, Epoch},
+};
+
+type E = Bls12_381;
+
+const NUM_PARTICIPANTS: usize = 4;
+const NUM_FAULTS: usize = 1;
+const EPOCH: u64 = 7;
+
+fn epoch() -> Epoch<E> {
+    test_support::epoch(NUM_PARTICIPANTS, NUM_FAULTS, EPOCH)
+}
+
+fn verify(epoch: Epoch<E>) -> (Vec<usize>, Vec<usize>) {
+    let (valid, invalid) = verify_batch(
+        &epoch.config,
+        &epoch.commitments,
+        &epoch.precomputed,
+        &epoch.generator,
+        epoch.messages,
+    );
+    let ids = |messages: Vec<SigmaMessage<E>>| {
+        let mut ids = messages
+            .iter()
+            .map(|message| message.id)
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids
+    };
+
+    (ids(valid), ids(invalid))
+}
+
+#[test]
+fn test_verify_batch_accepts_honest_sigmas() {
+    let (valid, invalid) = verify(epoch());
+
+    assert_eq!(valid, vec![0, 1, 2, 3]);
+    assert!(invalid.is_empty());
+}
+
+#[test]
+fn test_verify_batch_rejects_wrong_share() {
+    let mut epoch = epoch();
+    epoch.messages[2].sigma.1 = epoch.messages[0].sigma.1;
+
+    let (valid, invalid) = verify(epoch);
+
+    assert_eq!(valid, vec![0, 1, 3]);
+    assert_eq!(invalid, vec![2]);
+}
+
+#[test]
+fn test_verify_batch_rejects_share_outside_target_group() {
+    // -sigma_1 keeps the DLEQ proof valid, and the factor -1 vanishes from the batched check
+    // whenever its random weight is even.
+    let mut epoch = epoch();
+    epoch.messages[1].sigma.1 = epoch.messages[1].sigma.1.neg();
+
+    let (valid, invalid) = verify(epoch);
+
+    assert_eq!(valid, vec![0, 2, 3]);
+    assert_eq!(invalid, vec![1]);
+}
+
+/// The beacon output reconstructed from the sigmas of the given contributors.
+fn output(epoch: &Epoch<E>, contributors: &[usize]) -> BeaconOutput<E> {
+    let shares = contributors
+        .iter()
+        .map(|id| epoch.messages[*id].clone())
+        .collect::<Vec<_>>();
+    let evals = shares
+        .iter()
+        .map(|message| (message.id, message.sigma.1))
+        .collect::<Vec<_>>();
+    let sigma = reconstruct_sigma(&epoch.config, &evals).unwrap();
+
+    BeaconOutput {
+        epoch: EPOCH,
+        value: beacon_value::<E>(&sigma),
+        sigma,
+        contributors: contributors.to_vec(),
+        shares,
+    }
+}
+
+fn qual() -> HashSet<usize> {
+    (0..NUM_PARTICIPANTS).collect()
+}
+
+fn verify_output(epoch: &Epoch<E>, qual: &HashSet<usize>, output: &BeaconOutput<E>) -> bool {
+    verify_beacon(
+        &epoch.config,
+        &epoch.commitments,
+        &epoch.precomputed,
+        qual,
+        output.epoch,
+        output,
+    )
+}
+
+#[test]
+fn test_verify_beacon_accepts_reconstructed_output() {
+    let epoch = epoch();
+    let output = output(&epoch, &[0, 1]);
+
+    assert!(verify_output(&epoch, &qual(), &output));
+    // Any t + 1 contributors reconstruct the same value.
+    assert_eq!(self::output(&epoch, &[2, 3]).value, output.value);
+}
+
+#[test]
+fn test_verify_beacon_rejects_tampered_value() {
+    let epoch = epoch();
+    let mut output = output(&epoch, &[0, 1]);
+    output.value[0] ^= 1;
+
+    assert!(!verify_output(&epoch, &qual(), &output));
+}
+
+#[test]
+fn test_verify_beacon_rejects_wrong_epoch() {
+    let epoch = epoch();
+    let mut output = output(&epoch, &[0, 1]);
+
+    assert!(!verify_beacon(
+        &epoch.config,
+        &epoch.commitments,
+        &epoch.precomputed,
+        &qual(),
+        EPOCH + 1,
+        &output,
+    ));
+
+    // The sigmas still belong to the original epoch.
+    output.epoch = EPOCH + 1;
+    assert!(!verify_output(&epoch, &qual(), &output));
+}
+
+#[test]
+fn test_verify_beacon_rejects_contributor_outside_qual() {
+    let epoch = epoch();
+    let output = output(&epoch, &[0, 1]);
+    let qual = [0, 2, 3].into_iter().collect();
+
+    assert!(!verify_output(&epoch, &qual, &output));
+}
+
+#[test]
+fn test_verify_beacon_rejects_too_few_shares() {
+    let epoch = epoch();
+    let mut output = output(&epoch, &[0, 1]);
+    output.shares.pop();
+    output.contributors.pop();
+
+    assert!(!verify_output(&epoch, &qual(), &output));
+}
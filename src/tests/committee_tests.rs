@@ -0,0 +1,127 @@
+use ark_bls12_381::Bls12_381;
+use rand::thread_rng;
+
+use super::{Committee, CommitteeError, CommitteeFile, Member, Parameters, MAX_PIPELINE};
+use crate::config::{generate_config, NodeIdentity, SetupError};
+
+type E = Bls12_381;
+
+fn members(n: usize) -> Vec<Member> {
+    (0..n)
+        .map(|id| {
+            let mut member = NodeIdentity::<E>::generate(&mut thread_rng()).member();
+            member.id = id;
+            member.port = 9000 + id as u16;
+            member
+        })
+        .collect()
+}
+
+fn committee(parameters: Parameters, members: Vec<Member>) -> Result<Committee<E>, CommitteeError> {
+    Committee::new(CommitteeFile {
+        parameters,
+        members,
+    })
+}
+
+#[test]
+fn duplicate_ids() {
+    let mut members = members(4);
+    members[3].id = 1;
+
+    assert!(matches!(
+        committee(Parameters::default(), members),
+        Err(CommitteeError::DuplicateId(1))
+    ));
+}
+
+#[test]
+fn non_contiguous_ids() {
+    let mut members = members(4);
+    members[2].id = 5;
+
+    assert!(matches!(
+        committee(Parameters::default(), members),
+        Err(CommitteeError::MissingId(2))
+    ));
+}
+
+#[test]
+fn members_are_ordered_by_id() {
+    let mut members = members(4);
+    members.reverse();
+
+    let committee = committee(Parameters::default(), members).unwrap();
+    let ids = committee
+        .members
+        .iter()
+        .map(|member| member.id)
+        .collect::<Vec<_>>();
+    assert_eq!(ids, vec![0, 1, 2, 3]);
+}
+
+#[test]
+fn threshold_exceeds_committee() {
+    let committee = committee(Parameters::default(), members(4)).unwrap();
+    let dir = std::env::temp_dir().join(format!("committee-{}", std::process::id()));
+
+    // The threshold is checked before anything is written.
+    assert!(matches!(
+        generate_config(&dir, &committee, 4, false),
+        Err(SetupError::Invalid(_))
+    ));
+    assert!(!dir.exists());
+}
+
+#[test]
+fn pipeline_above_maximum() {
+    let parameters = Parameters {
+        pipeline: MAX_PIPELINE + 1,
+        ..Parameters::default()
+    };
+    assert!(matches!(
+        committee(parameters, members(4)),
+        Err(CommitteeError::InvalidPipeline(pipeline)) if pipeline == MAX_PIPELINE + 1
+    ));
+
+    let parameters = Parameters {
+        pipeline: MAX_PIPELINE,
+        ..Parameters::default()
+    };
+    assert!(committee(parameters, members(4)).is_ok());
+}
+
+#[test]
+fn hash_is_stable() {
+    let members = members(4);
+    let hash = committee(Parameters::default(), members.clone())
+        .unwrap()
+        .hash();
+
+    // Neither the order in the file nor hosts, ports and labels change the hash.
+    let mut moved = members.clone();
+    moved.reverse();
+    for member in &mut moved {
+        member.host = "example.com".to_string();
+        member.port += 100;
+        member.label = Some(format!("node {}", member.id));
+    }
+    assert_eq!(
+        committee(Parameters::default(), moved).unwrap().hash(),
+        hash
+    );
+
+    // The parameters and the keys do.
+    let parameters = Parameters {
+        encrypt: true,
+        ..Parameters::default()
+    };
+    assert_ne!(committee(parameters, members.clone()).unwrap().hash(), hash);
+
+    let mut rekeyed = members;
+    rekeyed[0].noise_key = rekeyed[1].noise_key.clone();
+    assert_ne!(
+        committee(Parameters::default(), rekeyed).unwrap().hash(),
+        hash
+    );
+}
@@ -0,0 +1,92 @@
+use ark_bls12_377::Bls12_377;
+use ark_bls12_381::Bls12_381;
+use std::fs;
+
+use super::{seal, unseal, ContainerError, Header, Kind, HEADER_LENGTH, VERSION};
+use crate::config::{generate_identity, read_identity, SetupError};
+
+fn header() -> Header {
+    Header {
+        curve: 1,
+        kind: Kind::Commitments,
+        num_participants: 4,
+        num_faults: 1,
+        committee: [7; 32],
+    }
+}
+
+fn sealed() -> Vec<u8> {
+    seal(&header(), b"payload")
+}
+
+#[test]
+fn round_trip() {
+    let bytes = sealed();
+    let (header, payload) = unseal(&bytes).unwrap();
+
+    assert_eq!(header, self::header());
+    assert_eq!(payload, b"payload");
+}
+
+#[test]
+fn bad_magic() {
+    let mut bytes = sealed();
+    bytes[0] = b'X';
+
+    assert!(matches!(unseal(&bytes), Err(ContainerError::BadMagic)));
+    assert!(matches!(unseal(b"GR"), Err(ContainerError::BadMagic)));
+}
+
+#[test]
+fn unsupported_version() {
+    let mut bytes = sealed();
+    bytes[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+
+    assert!(matches!(
+        unseal(&bytes),
+        Err(ContainerError::UnsupportedVersion(version)) if version == VERSION + 1
+    ));
+}
+
+#[test]
+fn checksum_mismatch() {
+    let mut bytes = sealed();
+    bytes[HEADER_LENGTH] ^= 1;
+
+    assert!(matches!(unseal(&bytes), Err(ContainerError::Checksum)));
+}
+
+#[test]
+fn truncated_header() {
+    let bytes = sealed();
+
+    assert!(matches!(
+        unseal(&bytes[..HEADER_LENGTH - 1]),
+        Err(ContainerError::Truncated)
+    ));
+}
+
+#[test]
+fn truncated_payload() {
+    let mut bytes = sealed();
+    bytes.remove(HEADER_LENGTH);
+
+    assert!(matches!(unseal(&bytes), Err(ContainerError::Truncated)));
+}
+
+#[test]
+fn wrong_curve() {
+    let path = std::env::temp_dir().join(format!("identity-{}.bin", std::process::id()));
+    generate_identity::<Bls12_381>(&path, true).unwrap();
+
+    let result = read_identity::<Bls12_377>(&path);
+    let own = read_identity::<Bls12_381>(&path);
+    let _ = fs::remove_file(&path);
+
+    match result {
+        Err(SetupError::Invalid(reason)) => assert!(reason.contains("BLS12-381")),
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("identity read for the wrong curve"),
+    }
+    assert!(own.is_ok());
+}
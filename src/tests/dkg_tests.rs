@@ -0,0 +1,142 @@
+use ark_bls12_381::Bls12_381;
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{Field, UniformRand};
+use rand::thread_rng;
+
+use optrand_pvss::{
+    modified_scrape::{config::Config, srs::SRS},
+    EncGroup, Scalar,
+};
+
+use super::{aggregate, deal, verify_dealing, BaseTable, Dealing};
+
+type E = Bls12_381;
+
+const NUM_PARTICIPANTS: usize = 4;
+const CONTEXT: &[u8] = b"test";
+
+struct Setup {
+    config: Config<E>,
+    table: BaseTable<E>,
+    secret_keys: Vec<Scalar<E>>,
+    public_keys: Vec<EncGroup<E>>,
+}
+
+fn setup() -> Setup {
+    let rng = &mut thread_rng();
+    let config = Config {
+        srs: SRS::<E>::setup(rng).unwrap(),
+        degree: 1,
+        num_participants: NUM_PARTICIPANTS,
+    };
+    let secret_keys = (0..NUM_PARTICIPANTS)
+        .map(|_| Scalar::<E>::rand(rng))
+        .collect::<Vec<_>>();
+    let public_keys = secret_keys
+        .iter()
+        .map(|x| config.srs.g1.mul(*x).into_affine())
+        .collect();
+
+    Setup {
+        table: BaseTable::new(config.srs.g2, NUM_PARTICIPANTS),
+        config,
+        secret_keys,
+        public_keys,
+    }
+}
+
+impl Setup {
+    fn deal(&self, id: usize) -> Dealing<E> {
+        deal(
+            &self.config,
+            &self.table,
+            &self.public_keys,
+            CONTEXT,
+            id,
+            &mut thread_rng(),
+        )
+    }
+
+    fn verify(&self, dealing: &Dealing<E>) -> bool {
+        verify_dealing(
+            &self.config,
+            &self.public_keys,
+            CONTEXT,
+            dealing,
+            &mut thread_rng(),
+        )
+    }
+}
+
+#[test]
+fn honest_dealing_is_valid() {
+    let setup = setup();
+    for id in 0..NUM_PARTICIPANTS {
+        assert!(setup.verify(&setup.deal(id)));
+    }
+}
+
+#[test]
+fn aggregated_shares_match_commitments() {
+    let setup = setup();
+    let dealings = (0..NUM_PARTICIPANTS)
+        .map(|id| setup.deal(id))
+        .collect::<Vec<_>>();
+    let core = aggregate(&dealings, NUM_PARTICIPANTS);
+
+    assert_eq!(core.encs.len(), NUM_PARTICIPANTS);
+    assert_eq!(core.comms.len(), NUM_PARTICIPANTS);
+    for i in 0..NUM_PARTICIPANTS {
+        // g1^sum f(i), decrypted with the participant's key.
+        let share = core.encs[i]
+            .mul(setup.secret_keys[i].inverse().unwrap())
+            .into_affine();
+        assert_eq!(
+            E::pairing(share, setup.config.srs.g2),
+            E::pairing(setup.config.srs.g1, core.comms[i])
+        );
+    }
+}
+
+#[test]
+fn tampered_share_is_rejected() {
+    let setup = setup();
+    let mut dealing = setup.deal(0);
+    dealing.encs[1] = dealing.encs[2];
+
+    assert!(!setup.verify(&dealing));
+}
+
+#[test]
+fn dealing_of_another_dealer_is_rejected() {
+    let setup = setup();
+    let mut dealing = setup.deal(0);
+    dealing.id = 2;
+
+    assert!(!setup.verify(&dealing));
+}
+
+#[test]
+fn dealing_of_higher_degree_is_rejected() {
+    let setup = setup();
+    let mut dealing = setup.deal(0);
+
+    // Shift the last share consistently in the encryption and the commitment: the pairing checks
+    // still pass and f(0), interpolated from the first t + 1 commitments, is unchanged, but the
+    // commitments no longer lie on a polynomial of degree t.
+    let delta = Scalar::<E>::rand(&mut thread_rng());
+    let last = NUM_PARTICIPANTS - 1;
+    dealing.encs[last] = dealing.encs[last] + setup.public_keys[last].mul(delta).into_affine();
+    dealing.comms[last] = dealing.comms[last] + setup.config.srs.g2.mul(delta).into_affine();
+
+    assert!(!setup.verify(&dealing));
+}
+
+#[test]
+fn dealing_of_wrong_length_is_rejected() {
+    let setup = setup();
+    let mut dealing = setup.deal(0);
+    dealing.comms.pop();
+
+    assert!(!setup.verify(&dealing));
+}
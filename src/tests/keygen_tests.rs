@@ -0,0 +1,117 @@
+use ark_bls12_381::Bls12_381;
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{Field, One, Zero};
+use rand::thread_rng;
+use std::net::TcpListener;
+
+use optrand_pvss::{
+    modified_scrape::{config::Config, srs::SRS},
+    ComGroup, Scalar,
+};
+
+use super::run;
+use crate::{
+    config::{Committee, CommitteeFile, NodeIdentity, Parameters},
+    network::RetransmitPolicy,
+};
+
+type E = Bls12_381;
+
+const NUM_PARTICIPANTS: usize = 4;
+
+/// g2^F(0), interpolated from the commitments to the shares of the given ids.
+fn group_key(pks: &[ComGroup<E>], ids: &[usize]) -> ComGroup<E> {
+    let mut key = <ComGroup<E> as AffineCurve>::Projective::zero();
+    for i in ids {
+        let x_i = Scalar::<E>::from(*i as u64 + 1);
+        let mut lambda = Scalar::<E>::one();
+        for j in ids.iter().filter(|j| *j != i) {
+            let x_j = Scalar::<E>::from(*j as u64 + 1);
+            lambda *= x_j * (x_j - x_i).inverse().unwrap();
+        }
+
+        key += &pks[*i].mul(lambda);
+    }
+
+    key.into_affine()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn nodes_generate_the_same_group_key() {
+    let identities = (0..NUM_PARTICIPANTS)
+        .map(|_| NodeIdentity::<E>::generate(&mut thread_rng()))
+        .collect::<Vec<_>>();
+    let members = identities
+        .iter()
+        .enumerate()
+        .map(|(id, identity)| {
+            let port = TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap()
+                .port();
+            let mut member = identity.member();
+            member.id = id;
+            member.port = port;
+            member
+        })
+        .collect();
+    let committee = Committee::<E>::new(CommitteeFile {
+        parameters: Parameters::default(),
+        members,
+    })
+    .unwrap();
+    let nodes = committee.resolve().unwrap();
+    let config = Config {
+        srs: SRS::<E>::setup(&mut thread_rng()).unwrap(),
+        degree: 1,
+        num_participants: NUM_PARTICIPANTS,
+    };
+
+    let handles = identities
+        .into_iter()
+        .enumerate()
+        .map(|(id, identity)| {
+            let config = config.clone();
+            let committee = committee.clone();
+            let nodes = nodes.clone();
+            tokio::spawn(async move {
+                run(
+                    id,
+                    &config,
+                    &committee,
+                    nodes.clone(),
+                    nodes[id],
+                    identity,
+                    RetransmitPolicy::default(),
+                )
+                .await
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut outputs = Vec::new();
+    for handle in handles {
+        outputs.push(handle.await.unwrap().unwrap());
+    }
+
+    for output in &outputs[1..] {
+        assert_eq!(output.pks, outputs[0].pks);
+        for (commitment, other) in output.commitments.iter().zip(&outputs[0].commitments) {
+            assert_eq!(commitment.id, other.id);
+            assert_eq!(commitment.part1, other.part1);
+            assert_eq!(commitment.part2, other.part2);
+        }
+    }
+    for (id, output) in outputs.iter().enumerate() {
+        assert_eq!(output.keystore.id, id);
+        assert_eq!(
+            E::pairing(output.keystore.sk, config.srs.g2),
+            E::pairing(config.srs.g1, output.pks[id])
+        );
+    }
+
+    let pks = &outputs[0].pks;
+    assert_eq!(group_key(pks, &[0, 1]), group_key(pks, &[2, 3]));
+    assert_eq!(group_key(pks, &[0, 3]), group_key(pks, &[1, 2]));
+}
@@ -0,0 +1,106 @@
+use ark_bls12_381::Bls12_381;
+use ark_ff::UniformRand;
+use rand::{thread_rng, Rng};
+use std::{
+    fs::{self, OpenOptions},
+    path::PathBuf,
+};
+
+use optrand_pvss::GT;
+
+use crate::{beacon::BeaconOutput, store::BeaconStore};
+
+type E = Bls12_381;
+
+/// A fresh store file, removed again when dropped.
+struct TempPath(PathBuf);
+
+impl TempPath {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("store-{}-{}.bin", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        Self(path)
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn output(epoch: u64) -> BeaconOutput<E> {
+    let rng = &mut thread_rng();
+    BeaconOutput {
+        epoch,
+        value: rng.gen(),
+        sigma: GT::<E>::rand(rng),
+        contributors: Vec::new(),
+        shares: Vec::new(),
+    }
+}
+
+fn epochs(outputs: &[BeaconOutput<E>]) -> Vec<u64> {
+    outputs.iter().map(|output| output.epoch).collect()
+}
+
+#[tokio::test]
+async fn latest_survives_reopen() {
+    let path = TempPath::new("latest");
+    let outputs = (1..=3).map(output).collect::<Vec<_>>();
+    {
+        let mut store = BeaconStore::<E>::open(&path.0).unwrap();
+        assert!(store.latest().unwrap().is_none());
+        for output in &outputs {
+            store.append(output).unwrap();
+        }
+    }
+
+    let store = BeaconStore::<E>::open(&path.0).unwrap();
+    let latest = store.latest().unwrap().unwrap();
+    assert_eq!(latest.epoch, 3);
+    assert_eq!(latest.value, outputs[2].value);
+    assert_eq!(latest.sigma, outputs[2].sigma);
+    assert_eq!(store.get(2).unwrap().unwrap().value, outputs[1].value);
+    assert!(store.get(4).unwrap().is_none());
+}
+
+#[tokio::test]
+async fn range_returns_stored_epochs_in_order() {
+    let path = TempPath::new("range");
+    let mut store = BeaconStore::<E>::open(&path.0).unwrap();
+    for epoch in [1, 2, 3, 5, 8] {
+        store.append(&output(epoch)).unwrap();
+    }
+
+    assert_eq!(epochs(&store.range(2..6).unwrap()), vec![2, 3, 5]);
+    assert_eq!(epochs(&store.range(4..=8).unwrap()), vec![5, 8]);
+    assert_eq!(epochs(&store.range(..).unwrap()), vec![1, 2, 3, 5, 8]);
+    assert!(store.range(9..).unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn truncated_tail_is_cut_off() {
+    let path = TempPath::new("truncated");
+    {
+        let mut store = BeaconStore::<E>::open(&path.0).unwrap();
+        store.append(&output(1)).unwrap();
+        store.append(&output(2)).unwrap();
+    }
+
+    // Cut the last record short, as a crash in the middle of a write would.
+    let size = fs::metadata(&path.0).unwrap().len();
+    let file = OpenOptions::new().write(true).open(&path.0).unwrap();
+    file.set_len(size - 10).unwrap();
+    drop(file);
+
+    let mut store = BeaconStore::<E>::open(&path.0).unwrap();
+    assert_eq!(store.latest().unwrap().unwrap().epoch, 1);
+    assert!(store.get(2).unwrap().is_none());
+
+    // New records go right behind the last complete one.
+    store.append(&output(2)).unwrap();
+    drop(store);
+    let store = BeaconStore::<E>::open(&path.0).unwrap();
+    assert_eq!(epochs(&store.range(..).unwrap()), vec![1, 2]);
+}